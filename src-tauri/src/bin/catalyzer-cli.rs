@@ -0,0 +1,781 @@
+//! Headless command-line interface for Catalyzer.
+//!
+//! Reads and writes the same `setting.yaml` as the GUI, so setups can be scripted
+//! (e.g. in CI containers) without a webview.
+use anyhow::{anyhow, bail, Context as _, Result};
+use catalyzer_lib::cdda::launch::launch_profile;
+use catalyzer_lib::cdda::release::changelog::changelog;
+use catalyzer_lib::cdda::release::commands::{ReleaseInfo, ReleaseList};
+use catalyzer_lib::cdda::session::{start_session, wait_session};
+use catalyzer_lib::files::commands::{link_to_target_subdir, unlink_target_subdir};
+use catalyzer_lib::git::commands::git_clone_mod_repo;
+use catalyzer_lib::install::build::{build_game, BuildSystem};
+use catalyzer_lib::install::pipeline::install_release;
+use catalyzer_lib::install::update::check_updates;
+use catalyzer_lib::install::{retention, rollback};
+use catalyzer_lib::install::{GameInstall, ReleaseChannel};
+use catalyzer_lib::model::Mod;
+use catalyzer_lib::profile::{parse_game_path, LaunchOptions, Profile, Settings};
+use clap::{Parser, Subcommand};
+use serde::Serialize;
+use std::path::Path;
+use std::sync::atomic::AtomicBool;
+
+#[derive(Parser)]
+#[command(
+    name = "catalyzer-cli",
+    version,
+    about = "Headless Catalyzer mod manager & launcher"
+)]
+struct Cli {
+    /// Print results as JSON instead of plain text.
+    #[arg(long, global = true)]
+    json: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Manage profiles.
+    #[command(subcommand)]
+    Profile(ProfileCommand),
+    /// Manage mods of the active profile.
+    #[command(subcommand)]
+    Mod(ModCommand),
+    /// Browse CDDA releases.
+    #[command(subcommand)]
+    Release(ReleaseCommand),
+    /// Manage installed game builds.
+    #[command(subcommand)]
+    Install(InstallCommand),
+    /// Launch the game of the active profile and wait for it to exit.
+    Launch,
+}
+
+#[derive(Subcommand)]
+enum ProfileCommand {
+    /// List profiles.
+    List,
+    /// Add a profile and make it active.
+    Add {
+        name: String,
+        /// Path to cataclysm-tiles(.exe) or Cataclysm.app
+        #[arg(long)]
+        game_path: Option<String>,
+    },
+    /// Make a profile active. Accepts a profile id or name.
+    Switch { profile: String },
+    /// Remove a profile. Accepts a profile id or name.
+    Remove { profile: String },
+    /// Replace the extra launch arguments, environment variables and working directory of a profile.
+    /// Values may contain placeholders such as `{profile_root}` and `{game_dir}`.
+    SetLaunchOptions {
+        profile: String,
+        /// Extra argument passed to the game (repeatable).
+        #[arg(long = "arg", allow_hyphen_values = true)]
+        args: Vec<String>,
+        /// Environment variable in KEY=VALUE form (repeatable).
+        #[arg(long = "env")]
+        envs: Vec<String>,
+        #[arg(long)]
+        working_dir: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ModCommand {
+    /// List mods in the mod data directory.
+    List,
+    /// Install a mod to the active profile. Accepts a mod id or directory name.
+    Install { target: String },
+    /// Uninstall a mod from the active profile. Accepts a mod id or directory name.
+    Uninstall { target: String },
+    /// Clone a mod repository into the mod data directory.
+    Clone { url: String },
+}
+
+#[derive(Subcommand)]
+enum ReleaseCommand {
+    /// List releases (experimental builds unless --stable is given).
+    List {
+        #[arg(long)]
+        stable: bool,
+        #[arg(long, default_value_t = 10)]
+        num: usize,
+        /// Release source id (defaults to CDDA).
+        #[arg(long)]
+        source: Option<String>,
+    },
+    /// List all assets of a release, marking the one selected by default.
+    Assets {
+        tag: String,
+        /// Release source id (defaults to CDDA).
+        #[arg(long)]
+        source: Option<String>,
+    },
+    /// List the configured release sources.
+    Sources,
+    /// List the pull requests between two builds (tags or install ids), grouped by category,
+    /// and the changed game data. Deepens the local CDDA clone as needed.
+    Changelog {
+        from: String,
+        to: String,
+        /// Also list the changed files under data/json and data/mods.
+        #[arg(long)]
+        files: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum InstallCommand {
+    /// List installed builds and the profiles using them.
+    List,
+    /// Register a game build extracted to a directory.
+    Register {
+        install_dir: String,
+        /// Release tag of the build. The channel is inferred from it.
+        #[arg(long)]
+        tag: Option<String>,
+    },
+    /// Download and install a release, and register it.
+    Add {
+        tag: String,
+        /// Switch this profile (id or name) to the new build.
+        #[arg(long)]
+        profile: Option<String>,
+        /// Release source id (defaults to CDDA).
+        #[arg(long)]
+        source: Option<String>,
+        /// Asset name to download instead of the default one (see `release assets`).
+        #[arg(long)]
+        asset: Option<String>,
+    },
+    /// Remove a build from the registry. Files on disk are kept.
+    Unregister { install_id: String },
+    /// Switch a profile to another installed build. Accepts a profile id or name.
+    Use { profile: String, install_id: String },
+    /// Delete old builds according to the retention policy. Only reports what would be deleted
+    /// unless --apply is given. Builds used by a profile are never deleted.
+    Prune {
+        /// Newest builds to keep per channel (defaults to the configured policy).
+        #[arg(long)]
+        keep: Option<usize>,
+        /// Days an unused download stays in the cache (defaults to the configured policy).
+        #[arg(long)]
+        cache_days: Option<u64>,
+        #[arg(long)]
+        apply: bool,
+    },
+    /// Keep a build when pruning.
+    Pin { install_id: String },
+    /// Allow a pinned build to be pruned.
+    Unpin { install_id: String },
+    /// List profiles whose channel has a newer build.
+    Outdated,
+    /// Install the newest build of a profile's channel next to the current one, and switch to it.
+    Update { profile: String },
+    /// Switch a profile back to the build it played before the last switch.
+    Rollback {
+        profile: String,
+        /// Also restore the saves backed up at the switch.
+        #[arg(long)]
+        restore_saves: bool,
+    },
+    /// List the checkouts that can be built: the CDDA clone, then `build.checkouts`.
+    Sources,
+    /// Build the game from a source checkout (the CDDA clone by default) and register it.
+    /// Linux only.
+    Build {
+        /// A path, or the directory name of a checkout listed by `install sources`.
+        source_dir: Option<String>,
+        /// Switch this profile (id or name) to the build.
+        #[arg(long)]
+        profile: Option<String>,
+        /// Use cmake instead of make.
+        #[arg(long)]
+        cmake: bool,
+        /// Parallel jobs (defaults to the configured value, or the number of CPUs).
+        #[arg(long)]
+        jobs: Option<usize>,
+    },
+    /// Roll back automatically if a new build crashes on its first launch.
+    AutoRollback {
+        profile: String,
+        /// Turn automatic rollback off.
+        #[arg(long)]
+        off: bool,
+    },
+}
+
+fn main() {
+    let cli = Cli::parse();
+    if let Err(e) = run(cli) {
+        eprintln!("error: {:#}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run(cli: Cli) -> Result<()> {
+    match cli.command {
+        Command::Profile(cmd) => run_profile(cmd, cli.json),
+        Command::Mod(cmd) => run_mod(cmd, cli.json),
+        Command::Release(cmd) => run_release(cmd, cli.json),
+        Command::Install(cmd) => run_install(cmd, cli.json),
+        Command::Launch => run_launch(),
+    }
+}
+
+fn print_json<T: Serialize>(value: &T) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+fn find_profile<'a>(settings: &'a Settings, key: &str) -> Result<&'a Profile> {
+    settings
+        .profiles
+        .iter()
+        .find(|p| p.get_id() == key)
+        .or_else(|| settings.profiles.iter().find(|p| p.get_name() == key))
+        .ok_or_else(|| anyhow!("Profile not found: {}", key))
+}
+
+fn run_profile(cmd: ProfileCommand, json: bool) -> Result<()> {
+    let mut settings = Settings::new();
+    match cmd {
+        ProfileCommand::List => {
+            if json {
+                return print_json(&settings.profiles);
+            }
+            for p in &settings.profiles {
+                println!(
+                    "{} {}\t{}\t{}",
+                    if p.is_active() { "*" } else { " " },
+                    p.get_id(),
+                    p.get_name(),
+                    p.get_game_path()
+                        .map(|path| path.display().to_string())
+                        .unwrap_or_else(|| "-".to_string()),
+                );
+            }
+        }
+        ProfileCommand::Add { name, game_path } => {
+            let game_path = match game_path {
+                Some(path) => Some(
+                    parse_game_path(&path).ok_or_else(|| anyhow!("Invalid game path: {}", path))?,
+                ),
+                None => None,
+            };
+            let id = format!("{:x}", chrono::Utc::now().timestamp_micros());
+            let new_profile = Profile::new(id, name, game_path);
+            settings.add_profile(&new_profile);
+            if json {
+                return print_json(&new_profile);
+            }
+            println!("Added profile: {}", new_profile.get_id());
+        }
+        ProfileCommand::Switch { profile } => {
+            let id = find_profile(&settings, &profile)?.get_id().to_string();
+            settings.set_active_profile(id.clone())?;
+            println!("Switched to profile: {}", id);
+        }
+        ProfileCommand::Remove { profile } => {
+            let id = find_profile(&settings, &profile)?.get_id().to_string();
+            if id == "default" {
+                bail!("The default profile cannot be removed");
+            }
+            settings.remove_profile(id.clone());
+            println!("Removed profile: {}", id);
+        }
+        ProfileCommand::SetLaunchOptions {
+            profile,
+            args,
+            envs,
+            working_dir,
+        } => {
+            let id = find_profile(&settings, &profile)?.get_id().to_string();
+            let envs = envs
+                .iter()
+                .map(|kv| {
+                    kv.split_once('=')
+                        .map(|(k, v)| (k.to_string(), v.to_string()))
+                        .ok_or_else(|| anyhow!("Invalid environment variable: {}", kv))
+                })
+                .collect::<Result<_>>()?;
+            let options = LaunchOptions {
+                args,
+                envs,
+                working_dir,
+            };
+            settings.set_launch_options(&id, options)?;
+            println!("Updated launch options of profile: {}", id);
+        }
+    }
+    Ok(())
+}
+
+fn mod_dir_name(m: &Mod) -> &str {
+    Path::new(&m.local_path)
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or_default()
+}
+
+fn find_mod<'a>(mods: &'a [Mod], key: &str) -> Result<&'a Mod> {
+    mods.iter()
+        .find(|m| m.info.get_id().as_deref() == Some(key) || mod_dir_name(m) == key)
+        .ok_or_else(|| anyhow!("Mod not found: {}", key))
+}
+
+fn run_mod(cmd: ModCommand, json: bool) -> Result<()> {
+    let mut settings = Settings::new();
+    match cmd {
+        ModCommand::List => {
+            let mods = settings.scan_mods()?;
+            if json {
+                return print_json(&mods);
+            }
+            for m in &mods {
+                println!(
+                    "{} {}\t{}\t{}",
+                    if m.is_installed { "*" } else { " " },
+                    m.info.get_id().unwrap_or_default(),
+                    mod_dir_name(m),
+                    m.local_version
+                        .as_ref()
+                        .map(|v| v.branch_name.as_str())
+                        .unwrap_or("-"),
+                );
+            }
+        }
+        ModCommand::Install { target } => {
+            let mods = settings.scan_mods()?;
+            let target = find_mod(&mods, &target)?;
+            link_to_target_subdir(Path::new(&target.local_path), &settings.get_game_mod_dir())
+                .context("Failed to create symlink")?;
+            let profile = settings.get_active_profile();
+            settings.mutate_state_mod_status(&profile)?;
+            println!("Installed: {}", mod_dir_name(target));
+        }
+        ModCommand::Uninstall { target } => {
+            let mods = settings.scan_mods()?;
+            let target = find_mod(&mods, &target)?;
+            unlink_target_subdir(Path::new(&target.local_path), &settings.get_game_mod_dir())
+                .context("Failed to remove symlink")?;
+            let profile = settings.get_active_profile();
+            settings.mutate_state_mod_status(&profile)?;
+            println!("Uninstalled: {}", mod_dir_name(target));
+        }
+        ModCommand::Clone { url } => {
+            git_clone_mod_repo(url.clone()).map_err(|e| anyhow!(e))?;
+            let profile = settings.get_active_profile();
+            settings.mutate_state_mod_status(&profile)?;
+            println!("Cloned: {}", url);
+        }
+    }
+    Ok(())
+}
+
+fn run_release(cmd: ReleaseCommand, json: bool) -> Result<()> {
+    let settings = Settings::new();
+    match cmd {
+        ReleaseCommand::List {
+            stable,
+            num,
+            source,
+        } => {
+            let channel = if stable {
+                ReleaseChannel::Stable
+            } else {
+                ReleaseChannel::Experimental
+            };
+            let list: ReleaseList = settings.release_source(source.as_deref())?.list(
+                channel,
+                num,
+                &settings.asset_preference,
+            )?;
+            if json {
+                return print_json(&list);
+            }
+            if list.stale {
+                eprintln!(
+                    "warning: GitHub is unreachable or rate-limited. Showing cached data from {}.",
+                    list.fetched_at.with_timezone(&chrono::Local)
+                );
+            }
+            for r in &list.releases {
+                println!(
+                    "{}\t{}\t{}\t{}\t{}",
+                    r.tag_name,
+                    r.published_at
+                        .map(|t| t
+                            .with_timezone(&chrono::Local)
+                            .format("%Y-%m-%d")
+                            .to_string())
+                        .unwrap_or_else(|| "-".to_string()),
+                    r.size
+                        .map(|s| format!("{} MB", s / 1024 / 1024))
+                        .unwrap_or_else(|| "-".to_string()),
+                    r.download_count
+                        .map(|n| n.to_string())
+                        .unwrap_or_else(|| "-".to_string()),
+                    r.download_url
+                );
+            }
+        }
+        ReleaseCommand::Assets { tag, source } => {
+            let release = settings
+                .release_source(source.as_deref())?
+                .get_release_info(&tag, &settings.asset_preference)?;
+            if json {
+                return print_json(&release);
+            }
+            for a in &release.assets {
+                let v = &a.variant;
+                println!(
+                    "{}\t{}\t{:?}\t{:?}\t{:?}\t{}",
+                    if a.download_url == release.download_url {
+                        "*"
+                    } else {
+                        " "
+                    },
+                    a.name,
+                    v.platform,
+                    v.arch,
+                    v.frontend,
+                    if v.sounds { "sounds" } else { "-" },
+                );
+            }
+            if let Some(reason) = &release.selection_reason {
+                println!("selected: {}", reason);
+            }
+        }
+        ReleaseCommand::Sources => {
+            if json {
+                return print_json(&settings.release_sources);
+            }
+            for s in &settings.release_sources {
+                println!("{}\t{}", s.id, s.name);
+            }
+        }
+        ReleaseCommand::Changelog { from, to, files } => {
+            let tag_of = |id: String| match settings.get_install(&id) {
+                Some(install) => install
+                    .tag
+                    .clone()
+                    .ok_or_else(|| anyhow!("{} was not installed from a release", id)),
+                None => Ok(id),
+            };
+            let changelog =
+                changelog(&tag_of(from)?, &tag_of(to)?, &settings.cdda_sparse_checkout)?;
+            if json {
+                return print_json(&changelog);
+            }
+            for (category, commits) in &changelog.groups {
+                println!("## {:?}", category);
+                for c in commits {
+                    match c.pr_number {
+                        Some(pr) => println!("- {} (#{})", c.title, pr),
+                        None => println!("- {} ({})", c.title, &c.sha[..7]),
+                    }
+                }
+                println!();
+            }
+            if files {
+                for f in &changelog.changed_files {
+                    println!("{:?}\t{}", f.change, f.path);
+                }
+            }
+            println!(
+                "{} file(s) changed in data/json and data/mods",
+                changelog.changed_files.len()
+            );
+        }
+    }
+    Ok(())
+}
+
+fn run_install(cmd: InstallCommand, json: bool) -> Result<()> {
+    let mut settings = Settings::new();
+    match cmd {
+        InstallCommand::List => {
+            #[derive(Serialize)]
+            struct Entry<'a> {
+                #[serde(flatten)]
+                install: &'a GameInstall,
+                used_by: Vec<String>,
+            }
+            let entries = settings
+                .installs
+                .iter()
+                .map(|install| Entry {
+                    install,
+                    used_by: settings.profiles_using_install(&install.id),
+                })
+                .collect::<Vec<_>>();
+            if json {
+                return print_json(&entries);
+            }
+            for e in &entries {
+                println!(
+                    "{}\t{}\t{}\t{} MB\t{}",
+                    e.install.id,
+                    e.install
+                        .cdda_version
+                        .map(|v| v.to_string())
+                        .or(e.install.version.clone())
+                        .unwrap_or_else(|| "-".to_string()),
+                    e.install.install_dir.display(),
+                    e.install.size_bytes / 1024 / 1024,
+                    e.used_by.join(","),
+                );
+            }
+        }
+        InstallCommand::Register { install_dir, tag } => {
+            let channel = match &tag {
+                Some(tag) => ReleaseChannel::from_tag(tag),
+                None => ReleaseChannel::Local,
+            };
+            let install = GameInstall::detect(Path::new(&install_dir), tag, channel)?;
+            settings.register_install(install.clone());
+            if json {
+                return print_json(&install);
+            }
+            println!("Registered install: {}", install.id);
+        }
+        InstallCommand::Add {
+            tag,
+            profile,
+            source,
+            asset,
+        } => {
+            let profile_id = match &profile {
+                Some(profile) => Some(find_profile(&settings, profile)?.get_id().to_string()),
+                None => None,
+            };
+            let mut release = settings
+                .release_source(source.as_deref())?
+                .get_release_info(&tag, &settings.asset_preference)?;
+            if let Some(asset) = &asset {
+                release.select_asset(asset)?;
+            }
+            let install = install_with_progress(&release)?;
+            settings.register_install(install.clone());
+            if let Some(profile_id) = &profile_id {
+                settings.set_profile_install(profile_id, &install.id)?;
+            }
+            if json {
+                return print_json(&install);
+            }
+            println!(
+                "Installed {} to {}",
+                install.id,
+                install.install_dir.display()
+            );
+        }
+        InstallCommand::Unregister { install_id } => {
+            settings.unregister_install(&install_id)?;
+            println!("Unregistered install: {}", install_id);
+        }
+        InstallCommand::Use {
+            profile,
+            install_id,
+        } => {
+            let id = find_profile(&settings, &profile)?.get_id().to_string();
+            settings.set_profile_install(&id, &install_id)?;
+            println!("Profile {} now uses install: {}", id, install_id);
+        }
+        InstallCommand::Prune {
+            keep,
+            cache_days,
+            apply,
+        } => {
+            let mut policy = settings.retention.clone();
+            if let Some(keep) = keep {
+                policy.keep_per_channel = keep;
+            }
+            if let Some(days) = cache_days {
+                policy.download_cache_days = days;
+            }
+            let plan = if apply {
+                retention::apply(&mut settings, &policy)
+            } else {
+                retention::plan(&settings, &policy)
+            };
+            if json {
+                return print_json(&plan);
+            }
+            for install in &plan.remove {
+                println!(
+                    "{}\t{}\t{} MB",
+                    install.id,
+                    install.install_dir.display(),
+                    install.size_bytes / 1024 / 1024
+                );
+            }
+            println!(
+                "{} {} build(s), {} MB",
+                if apply { "Removed" } else { "Would remove" },
+                plan.remove.len(),
+                plan.freed_bytes / 1024 / 1024
+            );
+            println!(
+                "{} {} MB of cached downloads",
+                if apply { "Removed" } else { "Would remove" },
+                plan.cache_freed_bytes / 1024 / 1024
+            );
+        }
+        InstallCommand::Pin { install_id } => settings.set_install_pinned(&install_id, true)?,
+        InstallCommand::Unpin { install_id } => settings.set_install_pinned(&install_id, false)?,
+        InstallCommand::Outdated => {
+            let check = check_updates(&settings);
+            if let Some(until) = check.rate_limited_until {
+                eprintln!(
+                    "warning: rate-limited until {}. Some sources were not checked.",
+                    until
+                );
+            }
+            if json {
+                return print_json(&check.updates);
+            }
+            for u in &check.updates {
+                println!(
+                    "{}\t{}\t{}",
+                    u.profile_name, u.current_tag, u.latest.tag_name
+                );
+            }
+        }
+        InstallCommand::Update { profile } => {
+            let id = find_profile(&settings, &profile)?.get_id().to_string();
+            let update = check_updates(&settings)
+                .updates
+                .into_iter()
+                .find(|u| u.profile_id == id)
+                .ok_or_else(|| anyhow!("{} is up to date", profile))?;
+            // Another profile following the channel may have installed it already.
+            let installed = settings
+                .get_release_install(&update.latest.source, &update.latest.tag_name)
+                .cloned();
+            let install = match installed {
+                Some(install) => install,
+                None => {
+                    let install = install_with_progress(&update.latest)?;
+                    settings.register_install(install.clone());
+                    install
+                }
+            };
+            settings.set_profile_install(&id, &install.id)?;
+            if json {
+                return print_json(&install);
+            }
+            println!(
+                "Updated {} from {} to {}",
+                profile, update.current_tag, update.latest.tag_name
+            );
+        }
+        InstallCommand::Rollback {
+            profile,
+            restore_saves,
+        } => {
+            let id = find_profile(&settings, &profile)?.get_id().to_string();
+            let build = rollback::rollback(&mut settings, &id, restore_saves)?;
+            if json {
+                return print_json(&build);
+            }
+            println!(
+                "Rolled back {} to {}",
+                profile,
+                build
+                    .install_id
+                    .or(build.game_path.map(|p| p.display().to_string()))
+                    .unwrap_or_else(|| "-".to_string())
+            );
+        }
+        InstallCommand::Sources => {
+            let sources = settings.build.sources();
+            if json {
+                return print_json(&sources);
+            }
+            for source in sources {
+                println!("{}", source.display());
+            }
+        }
+        InstallCommand::Build {
+            source_dir,
+            profile,
+            cmake,
+            jobs,
+        } => {
+            let profile_id = match &profile {
+                Some(profile) => Some(find_profile(&settings, profile)?.get_id().to_string()),
+                None => None,
+            };
+            let mut build_settings = settings.build.clone();
+            if cmake {
+                build_settings.system = BuildSystem::Cmake;
+            }
+            build_settings.jobs = jobs.or(build_settings.jobs);
+            let source_dir = build_settings.resolve_source(source_dir.as_deref());
+            let cancel = AtomicBool::new(false);
+            let install = build_game(&source_dir, &build_settings, &cancel, |line| {
+                eprintln!("{}", line)
+            })?;
+            settings.register_install(install.clone());
+            if let Some(profile_id) = &profile_id {
+                settings.set_profile_install(profile_id, &install.id)?;
+            }
+            if json {
+                return print_json(&install);
+            }
+            println!("Built {}", install.game_path.display());
+        }
+        InstallCommand::AutoRollback { profile, off } => {
+            let id = find_profile(&settings, &profile)?.get_id().to_string();
+            settings.set_auto_rollback(&id, !off)?;
+        }
+    }
+    Ok(())
+}
+
+fn install_with_progress(release: &ReleaseInfo) -> Result<GameInstall> {
+    let cancel = AtomicBool::new(false);
+    let mut last_stage = None;
+    install_release(release, &cancel, |p| {
+        if last_stage != Some(p.stage) {
+            eprintln!("{:?}...", p.stage);
+            last_stage = Some(p.stage);
+        }
+    })
+}
+
+fn run_launch() -> Result<()> {
+    let settings = Settings::new();
+    let profile = settings.get_active_profile();
+    println!("Launching profile: {}", profile.get_name());
+    let child = launch_profile(&profile).map_err(|e| anyhow!(e))?;
+    let session = start_session(&profile, &child);
+    let session = wait_session(&profile, session, child);
+    println!(
+        "Game exited. exit code: {}, play time: {}s",
+        session
+            .exit_code
+            .map(|code| code.to_string())
+            .unwrap_or_else(|| "-".to_string()),
+        session.play_time_secs.unwrap_or_default()
+    );
+    if let Some(report) = &session.crash_report {
+        println!("Crash report: {}", report.display());
+    }
+    // The app or another command may have changed setting.yaml while the game was running.
+    let mut settings = Settings::new();
+    if let Some(build) = rollback::on_session_end(&mut settings, &session) {
+        println!(
+            "The new build crashed on its first launch. Rolled back to {}",
+            build.install_id.as_deref().unwrap_or("the previous build")
+        );
+    }
+    if session.success != Some(true) {
+        std::process::exit(session.exit_code.unwrap_or(1));
+    }
+    Ok(())
+}
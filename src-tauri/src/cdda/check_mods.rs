@@ -0,0 +1,205 @@
+//! Parser for the output of `cataclysm-tiles --check-mods`.
+use crate::prelude::*;
+use regex::Regex;
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A single problem reported by the game while checking mods.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ModDiagnostic {
+    /// The mod being checked when the problem was reported (or the mod directory the file belongs to).
+    pub mod_id: Option<String>,
+    pub severity: Severity,
+    pub message: String,
+    /// Source JSON file, as reported by the game.
+    pub file: Option<String>,
+    pub line: Option<u32>,
+    pub column: Option<u32>,
+}
+
+struct Patterns {
+    checking_mod: Regex,
+    debugmsg: Regex,
+    debugmsg_meta: Regex,
+    error_line: Regex,
+    position: Regex,
+    mod_dir: Regex,
+}
+
+impl Patterns {
+    fn new() -> Self {
+        Self {
+            // e.g. "Checking mod Aftershock [aftershock]"
+            checking_mod: Regex::new(r"^\s*Checking mod .*\[(?P<id>[^\]]+)\]\s*$").unwrap(),
+            // e.g. " DEBUG    : Json error: ..." / " WARNING : ..."
+            debugmsg: Regex::new(r"^\s*(?P<tag>DEBUG|ERROR|WARNING)\s*:\s*(?P<msg>.*)$").unwrap(),
+            // e.g. " FUNCTION : ...", " FILE     : ...", " LINE     : ...", " VERSION  : ..."
+            debugmsg_meta: Regex::new(r"^\s*(FUNCTION|FILE|LINE|VERSION)\s*:").unwrap(),
+            // e.g. "Error loading data: ...", "Json error: ...", "warning: ..."
+            error_line: Regex::new(
+                r"(?i)^\s*(?:(?P<warn>warning)|(?:json )?error)[^:]*:\s*(?P<msg>.*)$",
+            )
+            .unwrap(),
+            // e.g. "data/mods/foo/items.json:12:5"
+            position: Regex::new(r"(?P<file>[^\s:]+\.json):(?P<line>\d+)(?::(?P<col>\d+))?")
+                .unwrap(),
+            mod_dir: Regex::new(r"mods[/\\](?P<dir>[^/\\]+)[/\\]").unwrap(),
+        }
+    }
+}
+
+fn new_diagnostic(
+    patterns: &Patterns,
+    current_mod: &Option<String>,
+    severity: Severity,
+    message: &str,
+) -> ModDiagnostic {
+    let position = patterns.position.captures(message);
+    let file = position.as_ref().map(|c| c["file"].to_string());
+    let mod_id = current_mod.clone().or_else(|| {
+        file.as_ref()
+            .and_then(|f| patterns.mod_dir.captures(f))
+            .map(|c| c["dir"].to_string())
+    });
+    ModDiagnostic {
+        mod_id,
+        severity,
+        message: message.trim().to_string(),
+        line: position.as_ref().and_then(|c| c["line"].parse().ok()),
+        column: position
+            .as_ref()
+            .and_then(|c| c.name("col"))
+            .and_then(|m| m.as_str().parse().ok()),
+        file,
+    }
+}
+
+/// Parses the combined stdout/stderr of `--check-mods` into per-mod diagnostics.
+///
+/// Lines following a message (e.g. the JSON excerpt the game prints under a JSON error)
+/// are appended to that message.
+pub fn parse_check_mods_output(output: &str) -> Vec<ModDiagnostic> {
+    let patterns = Patterns::new();
+    let mut diagnostics: Vec<ModDiagnostic> = Vec::new();
+    let mut current_mod: Option<String> = None;
+    let mut in_message = false;
+
+    for line in output.lines() {
+        if let Some(c) = patterns.checking_mod.captures(line) {
+            current_mod = Some(c["id"].to_string());
+            in_message = false;
+            continue;
+        }
+        if patterns.debugmsg_meta.is_match(line) {
+            in_message = false;
+            continue;
+        }
+        if let Some(c) = patterns.debugmsg.captures(line) {
+            let severity = match &c["tag"] {
+                "WARNING" => Severity::Warning,
+                _ => Severity::Error,
+            };
+            let diagnostic = new_diagnostic(&patterns, &current_mod, severity, &c["msg"]);
+            diagnostics.push(diagnostic);
+            in_message = true;
+            continue;
+        }
+        if let Some(c) = patterns.error_line.captures(line) {
+            let severity = match c.name("warn") {
+                Some(_) => Severity::Warning,
+                None => Severity::Error,
+            };
+            let diagnostic = new_diagnostic(&patterns, &current_mod, severity, line);
+            diagnostics.push(diagnostic);
+            in_message = true;
+            continue;
+        }
+        if in_message && !line.trim().is_empty() {
+            let last = diagnostics.last_mut().unwrap();
+            if last.file.is_none() {
+                // The position is sometimes printed on the line after the message.
+                let updated = new_diagnostic(&patterns, &current_mod, last.severity, line);
+                if updated.file.is_some() {
+                    last.file = updated.file;
+                    last.line = updated.line;
+                    last.column = updated.column;
+                    last.mod_id = last.mod_id.take().or(updated.mod_id);
+                }
+            }
+            last.message.push('\n');
+            last.message.push_str(line.trim_end());
+        }
+    }
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn attributes_debug_messages_to_the_mod_being_checked() {
+        let output = "\
+Checking mod Aftershock [aftershock]
+ DEBUG    : Json error: data/mods/Aftershock/items.json:12:5: invalid id
+ FUNCTION : void JsonIn::error()
+ FILE     : src/json.cpp
+ LINE     : 1745
+ VERSION  : 0.H
+Checking mod Magiclysm [magiclysm]
+ WARNING  : spell \"fireball\" has no description
+";
+        let diagnostics = parse_check_mods_output(output);
+        assert_eq!(diagnostics.len(), 2);
+
+        let error = &diagnostics[0];
+        assert_eq!(error.mod_id.as_deref(), Some("aftershock"));
+        assert_eq!(error.severity, Severity::Error);
+        assert_eq!(
+            error.file.as_deref(),
+            Some("data/mods/Aftershock/items.json")
+        );
+        assert_eq!((error.line, error.column), (Some(12), Some(5)));
+        assert!(!error.message.contains("FUNCTION"));
+
+        let warning = &diagnostics[1];
+        assert_eq!(warning.mod_id.as_deref(), Some("magiclysm"));
+        assert_eq!(warning.severity, Severity::Warning);
+        assert_eq!(warning.message, "spell \"fireball\" has no description");
+        assert_eq!(warning.file, None);
+    }
+
+    #[test]
+    fn appends_the_following_lines_and_a_late_position() {
+        let output = "\
+Error loading data: duplicate definition
+data/mods/foo/monsters.json:40
+    \"id\": \"mon_zombie\",
+";
+        let diagnostics = parse_check_mods_output(output);
+        assert_eq!(diagnostics.len(), 1);
+        let error = &diagnostics[0];
+        assert_eq!(error.severity, Severity::Error);
+        // Without a "Checking mod" line, the mod comes from the file path.
+        assert_eq!(error.mod_id.as_deref(), Some("foo"));
+        assert_eq!(error.file.as_deref(), Some("data/mods/foo/monsters.json"));
+        assert_eq!((error.line, error.column), (Some(40), None));
+        assert_eq!(
+            error.message,
+            "Error loading data: duplicate definition\n\
+             data/mods/foo/monsters.json:40\n    \"id\": \"mon_zombie\","
+        );
+    }
+
+    #[test]
+    fn ignores_output_without_problems() {
+        let output = "Checking mod Dark Days Ahead [dda]\nLoading data...\n";
+        assert!(parse_check_mods_output(output).is_empty());
+        assert!(parse_check_mods_output("").is_empty());
+    }
+}
@@ -0,0 +1,76 @@
+use crate::cdda::session::GameSession;
+use crate::prelude::*;
+use crate::profile::Profile;
+use std::fs::File;
+use std::io::Write;
+
+const LAUNCHER_LOG_LINES: usize = 200;
+
+#[derive(Debug, Serialize)]
+struct ModEntry {
+    info: ModInfo,
+    local_version: Option<LocalVersion>,
+}
+
+/// Bundles the files needed to investigate a crash into `crash_report_<time>.zip` under the log directory.
+///
+/// The bundle contains:
+/// - `session.json`: the game build, exit status and play time
+/// - `mods.json`: the active mods and their local versions
+/// - `debug.log`, `crash.log`: copied from the profile's config directory (if present)
+/// - `launcher.log`: the tail of the launcher log
+pub fn create_crash_report(profile: &Profile, session: &GameSession) -> Result<PathBuf> {
+    let log_dir = crate::paths::log_dir();
+    std::fs::create_dir_all(&log_dir)?;
+    let report_path = log_dir.join(format!(
+        "crash_report_{}.zip",
+        chrono::Local::now().format("%Y%m%d_%H%M%S")
+    ));
+    info!("Creating crash report: {}", report_path.display());
+
+    let mut writer = ::zip::ZipWriter::new(File::create(&report_path)?);
+    let options = ::zip::write::FileOptions::default();
+
+    writer.start_file("session.json", options)?;
+    writer.write_all(serde_json::to_string_pretty(session)?.as_bytes())?;
+
+    let mods = profile
+        .get_mod_info(true)
+        .into_iter()
+        .map(|info| {
+            let id = info.get_id();
+            let local_version = profile
+                .get_mods()
+                .iter()
+                .find(|m| m.is_installed && id.is_some() && m.info.get_id() == id)
+                .and_then(|m| m.local_version.clone());
+            ModEntry {
+                info,
+                local_version,
+            }
+        })
+        .collect::<Vec<_>>();
+    writer.start_file("mods.json", options)?;
+    writer.write_all(serde_json::to_string_pretty(&mods)?.as_bytes())?;
+
+    let config_dir = profile.get_config_dir();
+    for name in ["debug.log", "crash.log"] {
+        let path = config_dir.join(name);
+        if !path.exists() {
+            debug!("Skipping {:?}: not found", path);
+            continue;
+        }
+        writer.start_file(name, options)?;
+        writer.write_all(&std::fs::read(&path)?)?;
+    }
+
+    writer.start_file("launcher.log", options)?;
+    writer.write_all(
+        crate::read_log_tail(LAUNCHER_LOG_LINES, true)
+            .join("\n")
+            .as_bytes(),
+    )?;
+
+    writer.finish()?;
+    Ok(report_path)
+}
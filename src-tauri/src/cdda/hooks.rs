@@ -0,0 +1,120 @@
+use crate::cdda::session::GameSession;
+use crate::prelude::*;
+use crate::profile::Profile;
+use std::process::Command;
+
+/// A hook run before the game starts or after it exits.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Hook {
+    /// A shell command (`sh -c` on Unix, `cmd /C` on Windows), run in the profile root directory.
+    /// The profile paths are passed as environment variables (`CATALYZER_PROFILE_ROOT`, `CATALYZER_SAVE_DIR`, ...).
+    Command { command: String },
+    /// Commits the changes of every installed mod that is a git repository.
+    CommitMods { message: Option<String> },
+}
+
+#[derive(Debug, Default, Deserialize, Serialize, Clone)]
+pub struct ProfileHooks {
+    #[serde(default)]
+    pub pre_launch: Vec<Hook>,
+    #[serde(default)]
+    pub post_exit: Vec<Hook>,
+}
+
+fn shell_command(command: &str) -> Command {
+    #[cfg(target_os = "windows")]
+    {
+        let mut cmd = Command::new("cmd");
+        cmd.args(["/C", command]);
+        cmd
+    }
+    #[cfg(unix)]
+    {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", command]);
+        cmd
+    }
+}
+
+fn run_command(command: &str, profile: &Profile, envs: &[(String, String)]) -> Result<()> {
+    info!("[hook] $ {}", command);
+    let output = shell_command(command)
+        .current_dir(profile.get_profile_root_dir())
+        .env("CATALYZER_PROFILE_ID", profile.get_id())
+        .env("CATALYZER_PROFILE_NAME", profile.get_name())
+        .env(
+            "CATALYZER_GAME_PATH",
+            profile.get_game_path().unwrap_or_default(),
+        )
+        .envs(profile.get_user_data_paths().env_vars())
+        .envs(envs.iter().map(|(k, v)| (k, v)))
+        .output()
+        .with_context(|| format!("Failed to run hook: {}", command))?;
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .for_each(|line| info!("[hook] {}", line));
+    String::from_utf8_lossy(&output.stderr)
+        .lines()
+        .for_each(|line| warn!("[hook] {}", line));
+    ensure!(
+        output.status.success(),
+        "Hook `{}` failed with {}",
+        command,
+        output.status
+    );
+    Ok(())
+}
+
+fn commit_mods(profile: &Profile, message: &Option<String>) -> Result<()> {
+    let message = message.clone().unwrap_or_else(|| {
+        format!(
+            "Changes committed by hook at {}",
+            chrono::Local::now().to_rfc3339()
+        )
+    });
+    for m in profile.get_mods().iter().filter(|m| m.is_installed) {
+        let Ok(repo) = crate::git::open(m.local_path.clone()) else {
+            debug!("[hook] Not a git repository, skipped: {}", m.local_path);
+            continue;
+        };
+        info!("[hook] Committing changes of {}", m.local_path);
+        crate::git::commit(&repo, &message).map_err(|e| anyhow!(e))?;
+    }
+    Ok(())
+}
+
+fn run_hook(hook: &Hook, profile: &Profile, envs: &[(String, String)]) -> Result<()> {
+    match hook {
+        Hook::Command { command } => run_command(command, profile, envs),
+        Hook::CommitMods { message } => commit_mods(profile, message),
+    }
+}
+
+/// Runs the profile's pre-launch hooks in order. Stops at the first failing hook.
+pub fn run_pre_launch(profile: &Profile) -> Result<(), String> {
+    for hook in &profile.get_hooks().pre_launch {
+        run_hook(hook, profile, &[])
+            .map_err(|e| format!("Pre-launch hook failed. Launch aborted: {:#}", e))?;
+    }
+    Ok(())
+}
+
+/// Runs the profile's post-exit hooks in order.
+/// The exit status of the game is passed as `CATALYZER_EXIT_CODE` (empty if killed by a signal).
+/// Failures are logged and do not stop the remaining hooks.
+pub fn run_post_exit(profile: &Profile, session: &GameSession) {
+    let envs = [
+        ("CATALYZER_SESSION_ID".to_string(), session.id.clone()),
+        (
+            "CATALYZER_EXIT_CODE".to_string(),
+            session
+                .exit_code
+                .map(|code| code.to_string())
+                .unwrap_or_default(),
+        ),
+    ];
+    for hook in &profile.get_hooks().post_exit {
+        run_hook(hook, profile, &envs).unwrap_or_else(|e| warn!("Post-exit hook failed: {:#}", e));
+    }
+}
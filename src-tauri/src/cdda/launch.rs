@@ -1,10 +1,230 @@
+use crate::prelude::*;
+use crate::profile::Profile;
+use std::collections::BTreeMap;
+use std::ffi::{OsStr, OsString};
+use std::process::{Child, Command, Output};
+
+/// Describes how to start the game process.
+///
+/// The game binary is spawned directly (no intermediate shell), so paths are never
+/// re-parsed and the returned [`Child`] is the game process itself.
+#[derive(Debug, Clone)]
+pub struct Launcher {
+    program: PathBuf,
+    args: Vec<OsString>,
+    working_dir: PathBuf,
+    envs: BTreeMap<OsString, OsString>,
+}
+
+impl Launcher {
+    pub fn new(program: impl Into<PathBuf>, working_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            program: program.into(),
+            args: Vec::new(),
+            working_dir: working_dir.into(),
+            envs: BTreeMap::new(),
+        }
+    }
+
+    /// Builds a launcher for the given game path (`cataclysm-tiles.exe`, `Cataclysm.app` or `cataclysm-tiles`)
+    /// that uses `userdata_path` as the game's user directory.
+    pub fn for_game(game_path: &Path, userdata_path: &Path) -> Result<Self, String> {
+        let mut userdir = userdata_path.as_os_str().to_os_string();
+        userdir.push(std::path::MAIN_SEPARATOR_STR);
+        Ok(platform_launcher(game_path)?.arg("--userdir").arg(userdir))
+    }
+
+    /// Builds a launcher for the game of the given profile.
+    pub fn for_profile(profile: &Profile) -> Result<Self, String> {
+        let game_path = profile
+            .get_game_path()
+            .ok_or_else(|| "Game path is not set".to_string())?;
+        profile.create_dir_if_unexist();
+        Self::for_game(&game_path, &profile.get_profile_root_dir())
+    }
+
+    pub fn arg(mut self, arg: impl AsRef<OsStr>) -> Self {
+        self.args.push(arg.as_ref().to_os_string());
+        self
+    }
+
+    pub fn args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        self.args
+            .extend(args.into_iter().map(|a| a.as_ref().to_os_string()));
+        self
+    }
+
+    pub fn env(mut self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) -> Self {
+        self.envs
+            .insert(key.as_ref().to_os_string(), value.as_ref().to_os_string());
+        self
+    }
+
+    pub fn current_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.working_dir = dir.into();
+        self
+    }
+
+    pub fn get_program(&self) -> &Path {
+        &self.program
+    }
+
+    /// Applies the profile's launch options, expanding placeholders like `{profile_root}` and `{game_dir}`.
+    pub fn with_launch_options(mut self, profile: &Profile) -> Self {
+        let options = profile.get_launch_options();
+        let vars = [
+            (
+                "profile_root",
+                profile.get_profile_root_dir().display().to_string(),
+            ),
+            ("profile_id", profile.get_id().to_string()),
+            ("profile_name", profile.get_name().to_string()),
+            (
+                "game_path",
+                profile
+                    .get_game_path()
+                    .map(|p| p.display().to_string())
+                    .unwrap_or_default(),
+            ),
+            ("game_dir", self.working_dir.display().to_string()),
+        ];
+        let expand = |template: &str| {
+            vars.iter()
+                .fold(template.to_string(), |acc, (name, value)| {
+                    acc.replace(&format!("{{{}}}", name), value)
+                })
+        };
+
+        self.args
+            .extend(options.args.iter().map(|a| expand(a).into()));
+        for (key, value) in &options.envs {
+            self.envs.insert(key.into(), expand(value).into());
+        }
+        if let Some(dir) = &options.working_dir {
+            self.working_dir = PathBuf::from(expand(dir));
+        }
+        self
+    }
+
+    fn command(&self) -> Command {
+        let mut command = Command::new(&self.program);
+        command
+            .args(&self.args)
+            .current_dir(&self.working_dir)
+            .envs(&self.envs);
+        command
+    }
+
+    /// Spawns the game process.
+    pub fn spawn(&self) -> Result<Child, String> {
+        debug!("Launching: {:?}", self);
+        let child = self
+            .command()
+            .spawn()
+            .map_err(|e| format!("Failed to launch the game: {}", e))?;
+        debug!("Game process started. pid: {}", child.id());
+        Ok(child)
+    }
+
+    /// Runs the game process to completion, collecting its stdout and stderr.
+    pub fn output(&self) -> Result<Output, String> {
+        debug!("Running: {:?}", self);
+        self.command()
+            .output()
+            .map_err(|e| format!("Failed to run the game: {}", e))
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn platform_launcher(game_path: &Path) -> Result<Launcher, String> {
+    if game_path.file_name().unwrap_or_default() != "cataclysm-tiles.exe" {
+        return Err(format!(
+            "Game path is not cataclysm-tiles.exe: {:?}",
+            game_path
+        ));
+    }
+    let game_dir = game_path.parent().unwrap();
+    Ok(Launcher::new(game_path, game_dir))
+}
+
+#[cfg(target_os = "macos")]
+fn platform_launcher(game_path: &Path) -> Result<Launcher, String> {
+    if game_path.extension().unwrap_or_default() != "app" {
+        return Err(format!("Game path does not exist: {:?}", game_path));
+    }
+    let resource_dir = game_path.join("Contents").join("Resources");
+    let binary = resource_dir.join("cataclysm-tiles");
+    ensure_executable(&binary)?;
+
+    // refer to: Cataclysm.app/Contents/MacOS/Cataclysm.sh
+    Ok(Launcher::new(&binary, &resource_dir)
+        .env("DYLD_LIBRARY_PATH", &resource_dir)
+        .env("DYLD_FRAMEWORK_PATH", &resource_dir))
+}
+
+#[cfg(target_os = "linux")]
+fn platform_launcher(game_path: &Path) -> Result<Launcher, String> {
+    if game_path.file_name().unwrap_or_default() != "cataclysm-tiles" {
+        return Err(format!("Game path is not cataclysm-tiles: {:?}", game_path));
+    }
+    // tarballの展開方法によっては実行権限が落ちている
+    ensure_executable(game_path)?;
+
+    // refer to: cataclysmdda-*/cataclysm-launcher
+    let game_dir = game_path.parent().unwrap();
+    Ok(Launcher::new(game_path, game_dir))
+}
+
+/// 実行権限付与
+#[cfg(unix)]
+fn ensure_executable(binary: &Path) -> Result<(), String> {
+    use std::os::unix::fs::PermissionsExt;
+    let mut permissions = std::fs::metadata(binary)
+        .map_err(|e| format!("Game binary not found: {:?}: {}", binary, e))?
+        .permissions();
+    if permissions.mode() & 0o111 != 0o111 {
+        permissions.set_mode(permissions.mode() | 0o111);
+        std::fs::set_permissions(binary, permissions)
+            .map_err(|e| format!("Failed to make {:?} executable: {}", binary, e))?;
+    }
+    Ok(())
+}
+
+/// Runs the profile's pre-launch hooks, then launches the game of the given profile
+/// with the profile's user directory and launch options.
+pub fn launch_profile(profile: &Profile) -> Result<Child, String> {
+    let launcher = Launcher::for_profile(profile)?;
+    crate::cdda::hooks::run_pre_launch(profile)?;
+    launcher.with_launch_options(profile).spawn()
+}
+
 pub mod commands {
-    use crate::files::commands::open_dir;
-    use crate::prelude::*;
-    use std::process::Command;
+    use super::*;
+    use crate::cdda::check_mods::{parse_check_mods_output, ModDiagnostic};
+    use crate::cdda::session::{track, GameSession, SessionState};
+    use crate::cdda::version::CddaVersion;
 
+    #[derive(Debug, Serialize, Clone)]
+    pub struct InspectModsResult {
+        pub targets: Vec<String>,
+        /// Version of the game that checked the mods, if known.
+        pub game_version: Option<CddaVersion>,
+        pub diagnostics: Vec<ModDiagnostic>,
+        /// Raw output of `--check-mods`, saved only if requested.
+        pub artifact: Option<PathBuf>,
+    }
+
+    /// Runs `--check-mods` for the active mods and returns the parsed diagnostics.
+    /// If `save_artifact` is true, the raw output is also written to `inspect_mods_<time>.txt` in the log directory.
     #[tauri::command]
-    pub fn inspect_mods(state: tauri::State<'_, AppState>) -> Result<(), String> {
+    pub fn inspect_mods(
+        state: tauri::State<'_, AppState>,
+        save_artifact: Option<bool>,
+    ) -> Result<InspectModsResult, String> {
         let settings = state.get_settings().unwrap();
         let profile = settings.get_active_profile();
         if profile.get_game_path().is_none() {
@@ -15,113 +235,78 @@ pub mod commands {
             .iter()
             .filter_map(|m| m.get_id())
             .collect::<Vec<_>>();
-        let chk_option = format!("--check-mods {}", target_mod_ids.join(" "));
-        let child = launch(
-            profile.get_game_path().unwrap(),
-            profile.get_profile_root_dir(),
-            Some(chk_option),
-        )?;
-        let output = child
-            .wait_with_output()
-            .map_err(|e| format!("Failed to wait for the process: {}", e))?
-            .stderr;
-        let output = String::from_utf8(output)
-            .map_err(|e| format!("Failed to convert the output to string: {}", e))?;
+        let output = Launcher::for_profile(&profile)?
+            .arg("--check-mods")
+            .args(&target_mod_ids)
+            .output()?;
+        let output = [output.stdout, output.stderr]
+            .iter()
+            .map(|bytes| String::from_utf8_lossy(bytes))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let diagnostics = parse_check_mods_output(&output);
+        let game_version = profile
+            .get_install_id()
+            .and_then(|id| settings.get_install(id))
+            .and_then(|install| install.cdda_version)
+            .or_else(|| {
+                profile
+                    .get_game_path()
+                    .and_then(|path| crate::install::detect_version(&path))
+                    .and_then(|v| v.parse().ok())
+            });
 
         let time = chrono::Local::now();
         warn!(
             r#"
 ===INSPECT MODS START===
 Targets: {target_mod_ids:?}
+Game version: {game_version:?}
 Date: {time}
 
 ===INSPECT MODS RESULT==
 {output}
 ================="#
         );
-        let log_file = crate::paths::log_dir().join(format!("inspect_mods_{:?}.txt", time));
-        std::fs::write(log_file.clone(), output.as_bytes())
-            .map_err(|e| format!("Failed to write to the log file: {}", e))?;
-        open_dir(log_file.to_string_lossy().into());
-        Ok(())
+        let artifact = if save_artifact.unwrap_or(false) {
+            let log_file = crate::paths::log_dir()
+                .join(format!("inspect_mods_{}.txt", time.format("%Y%m%d_%H%M%S")));
+            std::fs::write(&log_file, output.as_bytes())
+                .map_err(|e| format!("Failed to write to the log file: {}", e))?;
+            Some(log_file)
+        } else {
+            None
+        };
+        Ok(InspectModsResult {
+            targets: target_mod_ids,
+            game_version,
+            diagnostics,
+            artifact,
+        })
     }
 
+    /// Launches the game of the active profile. Refused while its game is already running.
+    /// The pre-launch hooks may take a while, so they run off the async runtime.
     #[tauri::command]
-    pub fn launch_game(state: tauri::State<'_, AppState>) -> Result<(), String> {
-        let setting = state.get_settings().unwrap();
-        let profile = setting.get_active_profile();
-
-        let game_path = profile.get_game_path();
-        let userdata_path = profile.get_profile_root_dir();
-
-        match game_path {
-            Some(path) => {
-                profile.create_dir_if_unexist();
-                launch(path, userdata_path, None)
-                    .map_err(|e| format!("Failed to launch the game: {}", e))?;
+    pub async fn launch_game(app: tauri::AppHandle) -> Result<GameSession, String> {
+        use tauri::Manager;
+        tauri::async_runtime::spawn_blocking(move || {
+            let profile = app
+                .state::<AppState>()
+                .get_settings()
+                .unwrap()
+                .get_active_profile();
+            if app
+                .state::<SessionState>()
+                .get_active(profile.get_id())
+                .is_some()
+            {
+                return Err("The game of this profile is already running".to_string());
             }
-            None => {
-                return Err("Game path is not set".to_string());
-            }
-        };
-        Ok(())
-    }
-
-    #[cfg(target_os = "windows")]
-    fn launch(
-        game_path: PathBuf,
-        userdata_path: PathBuf,
-        extra_option_string: Option<String>,
-    ) -> Result<std::process::Child, String> {
-        let options = format!(
-            "cd /d {} && start cataclysm-tiles.exe --userdir '{}\\' {}",
-            &game_path.parent().unwrap().to_string_lossy(),
-            userdata_path.to_string_lossy(),
-            extra_option_string.unwrap_or_default()
-        );
-        debug!("command: {}", &options);
-        let child = Command::new("cmd")
-            .args(["/C", &options])
-            .stdout(std::process::Stdio::piped())
-            .stderr(std::process::Stdio::piped())
-            .spawn()
-            .map_err(|e| format!("Failed to launch the game: {}", e))?;
-        Ok(child)
-    }
-
-    #[cfg(target_os = "macos")]
-    fn launch(
-        game_path: PathBuf,
-        userdata_path: PathBuf,
-        extra_option_string: Option<String>,
-    ) -> Result<std::process::Child, String> {
-        if game_path.extension().unwrap() != "app" {
-            return Err(format!("Game path does not exist: {:?}", game_path));
-        }
-        debug!("Launching game: {:?}", &game_path);
-
-        let resource_dir = game_path.join("Contents").join("Resources");
-        resource_dir.try_exists().unwrap();
-
-        // 実行権限付与
-        Command::new("chmod")
-            .args(["+x", resource_dir.join("cataclysm-tiles").to_str().unwrap()])
-            .spawn()
-            .map_err(|e| format!("Failed to launch the game: {}", e))?;
-
-        // refer to: Cataclysm.app/Contents/MacOS/Cataclysm.sh
-        let command = format!(
-            "cd '{}' && export DYLD_LIBRARY_PATH=. && export DYLD_FRAMEWORK_PATH=. && ./cataclysm-tiles --userdir '{}/' {}",
-            resource_dir.to_string_lossy(),
-            userdata_path.to_string_lossy(),
-            extra_option_string.unwrap_or_default()
-        );
-        debug!("command: {}", &command);
-        let child = Command::new("sh")
-            .arg("-c")
-            .arg(command)
-            .spawn()
-            .map_err(|e| format!("Failed to launch the game: {}", e))?;
-        Ok(child)
+            let child = launch_profile(&profile)?;
+            Ok(track(app, &profile, child))
+        })
+        .await
+        .map_err(|e| e.to_string())?
     }
 }
@@ -1,3 +1,8 @@
+pub mod check_mods;
+pub mod crash_report;
+pub mod hooks;
 pub mod launch;
 pub mod release;
+pub mod session;
 pub mod translation;
+pub mod version;
@@ -0,0 +1,390 @@
+//! Classification of release assets (platform, architecture, frontend, sounds) and the
+//! preference policy that picks the default asset of a release.
+use crate::prelude::*;
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Platform {
+    Windows,
+    Macos,
+    Linux,
+    Android,
+    Unknown,
+}
+
+impl Platform {
+    pub fn current() -> Self {
+        match crate::get_platform().as_str() {
+            "windows" => Platform::Windows,
+            "macos" => Platform::Macos,
+            "linux" => Platform::Linux,
+            _ => Platform::Unknown,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Arch {
+    X64,
+    X86,
+    Arm64,
+    /// macOS universal binary.
+    Universal,
+    Unknown,
+}
+
+impl Arch {
+    pub fn current() -> Self {
+        match std::env::consts::ARCH {
+            "x86_64" => Arch::X64,
+            "x86" => Arch::X86,
+            "aarch64" => Arch::Arm64,
+            _ => Arch::Unknown,
+        }
+    }
+
+    /// Whether a build for `self` runs on a `host` machine, natively or not.
+    fn runs_on(self, host: Arch) -> bool {
+        match (self, host) {
+            (a, b) if a == b => true,
+            (Arch::Universal, _) | (Arch::Unknown, _) => true,
+            (Arch::X86, Arch::X64) => true,
+            // Rosetta 2 / Windows on ARM emulation
+            (Arch::X64, Arch::Arm64) => true,
+            _ => false,
+        }
+    }
+
+    fn is_native_for(self, host: Arch) -> bool {
+        self == host || self == Arch::Universal
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Frontend {
+    Tiles,
+    Curses,
+    Unknown,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub struct AssetVariant {
+    pub platform: Platform,
+    pub arch: Arch,
+    pub frontend: Frontend,
+    /// Bundled with the sound pack.
+    pub sounds: bool,
+}
+
+/// Infers the variant from an asset name such as
+/// `cdda-windows-tiles-sounds-x64-msvc-2024-01-01-0000.zip` or `cdda-0.F-Windows_x64-Tiles.zip`.
+pub fn classify(name: &str) -> AssetVariant {
+    let name = name.to_lowercase();
+    let has = |keys: &[&str]| keys.iter().any(|k| name.contains(k));
+    let platform = if has(&["android", ".apk", ".aab"]) {
+        Platform::Android
+    } else if has(&["windows", "win64", "win32"]) {
+        Platform::Windows
+    } else if has(&["osx", "macos", "darwin"]) || name.ends_with(".dmg") {
+        Platform::Macos
+    } else if has(&["linux"]) {
+        Platform::Linux
+    } else {
+        Platform::Unknown
+    };
+    // x86_64 contains x86, so check 64 bit first.
+    let arch = if has(&["x64", "x86_64", "amd64"]) {
+        Arch::X64
+    } else if has(&["arm64", "aarch64"]) {
+        Arch::Arm64
+    } else if has(&["universal"]) {
+        Arch::Universal
+    } else if has(&["x86", "x32", "win32", "i686"]) {
+        Arch::X86
+    } else {
+        Arch::Unknown
+    };
+    let frontend = if has(&["curses", "terminal"]) {
+        Frontend::Curses
+    } else if has(&["tiles", "graphics"]) {
+        Frontend::Tiles
+    } else {
+        Frontend::Unknown
+    };
+    AssetVariant {
+        platform,
+        arch,
+        frontend,
+        sounds: has(&["sound"]),
+    }
+}
+
+/// A downloadable file attached to a release.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ClassifiedAsset {
+    pub name: String,
+    pub download_url: String,
+    pub size: Option<u64>,
+    /// e.g. `sha256:...`. GitHub reports it for assets uploaded recently.
+    pub digest: Option<String>,
+    #[serde(default)]
+    pub download_count: Option<u64>,
+    pub variant: AssetVariant,
+}
+
+impl ClassifiedAsset {
+    pub fn new(name: &str, download_url: &str, size: Option<u64>, digest: Option<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            download_url: download_url.to_string(),
+            size,
+            digest,
+            download_count: None,
+            variant: classify(name),
+        }
+    }
+}
+
+/// Which build the user wants by default. Unset fields use the defaults noted below.
+#[derive(Debug, Default, Deserialize, Serialize, Clone)]
+pub struct AssetPreference {
+    /// Defaults to tiles.
+    #[serde(default)]
+    pub frontend: Option<Frontend>,
+    /// Defaults to builds with sounds.
+    #[serde(default)]
+    pub sounds: Option<bool>,
+    /// Defaults to the architecture of this machine.
+    #[serde(default)]
+    pub arch: Option<Arch>,
+}
+
+/// The asset picked by `AssetPreference::choose`, and why.
+#[derive(Debug, Serialize, Clone)]
+pub struct AssetChoice {
+    pub index: usize,
+    pub reason: String,
+}
+
+impl AssetPreference {
+    /// Picks the default asset for this platform. `pattern` (the source's asset pattern)
+    /// admits assets whose platform can't be told from the name.
+    pub fn choose(
+        &self,
+        assets: &[ClassifiedAsset],
+        pattern: Option<&regex::Regex>,
+    ) -> Option<AssetChoice> {
+        let platform = Platform::current();
+        let host = self.arch.unwrap_or_else(Arch::current);
+        let frontend = self.frontend.unwrap_or(Frontend::Tiles);
+        let sounds = self.sounds.unwrap_or(true);
+
+        let (index, asset, score) = assets
+            .iter()
+            .enumerate()
+            .filter(|(_, a)| {
+                a.variant.platform == platform
+                    || (a.variant.platform == Platform::Unknown
+                        && pattern.is_some_and(|re| re.is_match(&a.name)))
+            })
+            .filter(|(_, a)| a.variant.arch.runs_on(host))
+            .map(|(i, a)| {
+                // Architecture first, then frontend, then sounds.
+                let score = ((a.variant.arch.is_native_for(host) as u8) << 2)
+                    | (((a.variant.frontend == frontend) as u8) << 1)
+                    | ((a.variant.sounds == sounds) as u8);
+                (i, a, score)
+            })
+            // max_by_key returns the last maximum. Prefer the first asset on ties.
+            .rev()
+            .max_by_key(|(_, _, score)| *score)?;
+
+        let v = &asset.variant;
+        let mut reasons = vec![format!("{:?} build", platform)];
+        reasons.push(match (score & 4 != 0, self.arch.is_some()) {
+            (true, true) => format!("{:?} (preferred)", v.arch),
+            (true, false) => format!("{:?} (this machine)", v.arch),
+            (false, _) => format!("{:?} (no native build for {:?})", v.arch, host),
+        });
+        reasons.push(match (score & 2 != 0, self.frontend.is_some()) {
+            (true, true) => format!("{:?} (preferred)", v.frontend),
+            (true, false) => format!("{:?} (default)", v.frontend),
+            (false, _) => format!("{:?} (no {:?} build)", v.frontend, frontend),
+        });
+        let with = |s: bool| if s { "with sounds" } else { "without sounds" };
+        reasons.push(match (score & 1 != 0, self.sounds.is_some()) {
+            (true, true) => format!("{} (preferred)", with(v.sounds)),
+            (true, false) => format!("{} (default)", with(v.sounds)),
+            (false, _) => format!("{} (no build {})", with(v.sounds), with(sounds)),
+        });
+        Some(AssetChoice {
+            index,
+            reason: format!("{}: {}", asset.name, reasons.join(", ").to_lowercase()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variant(platform: Platform, arch: Arch, frontend: Frontend, sounds: bool) -> AssetVariant {
+        AssetVariant {
+            platform,
+            arch,
+            frontend,
+            sounds,
+        }
+    }
+
+    #[test]
+    fn classifies_experimental_and_stable_names() {
+        use {Arch::*, Frontend::*, Platform::*};
+        let cases = [
+            (
+                "cdda-windows-tiles-sounds-x64-msvc-2024-01-01-0000.zip",
+                variant(Windows, X64, Tiles, true),
+            ),
+            (
+                "cdda-windows-tiles-x32-msvc-2024-01-01-0000.zip",
+                variant(Windows, X86, Tiles, false),
+            ),
+            (
+                "cdda-0.F-Windows_x64-Tiles.zip",
+                variant(Windows, X64, Tiles, false),
+            ),
+            (
+                "cdda-osx-tiles-universal-2024-01-01-0000.dmg",
+                variant(Macos, Universal, Tiles, false),
+            ),
+            (
+                "cdda-linux-with-graphics-and-sounds-x64-2024-01-01-0000.tar.gz",
+                variant(Linux, X64, Tiles, true),
+            ),
+            (
+                "cdda-linux-terminal-only-x86_64-2024-01-01-0000.tar.gz",
+                variant(Linux, X64, Curses, false),
+            ),
+            (
+                "cdda-android-bundle-2024-01-01-0000.aab",
+                variant(Android, Arch::Unknown, Frontend::Unknown, false),
+            ),
+            (
+                "Cataclysm.zip",
+                variant(Platform::Unknown, Arch::Unknown, Frontend::Unknown, false),
+            ),
+        ];
+        for (name, expected) in cases {
+            assert_eq!(classify(name), expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn arch_compatibility() {
+        assert!(Arch::X86.runs_on(Arch::X64));
+        assert!(Arch::X64.runs_on(Arch::Arm64));
+        assert!(Arch::Universal.runs_on(Arch::Arm64));
+        assert!(!Arch::Arm64.runs_on(Arch::X64));
+        assert!(!Arch::X64.runs_on(Arch::X86));
+    }
+
+    /// An asset of this platform.
+    fn asset(variant: &str) -> ClassifiedAsset {
+        let name = format!("cdda-{}-{}.zip", crate::get_platform(), variant);
+        ClassifiedAsset::new(&name, &format!("https://example.com/{}", name), None, None)
+    }
+
+    fn x64() -> AssetPreference {
+        AssetPreference {
+            arch: Some(Arch::X64),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn prefers_native_tiles_with_sounds() {
+        let assets = [
+            asset("terminal-only-x64"),
+            asset("tiles-x32"),
+            asset("tiles-x64"),
+            asset("tiles-sounds-x64"),
+        ];
+        let choice = x64().choose(&assets, None).unwrap();
+        assert_eq!(choice.index, 3);
+        assert!(
+            choice.reason.contains("x64 (preferred)"),
+            "{}",
+            choice.reason
+        );
+    }
+
+    #[test]
+    fn prefers_the_first_asset_on_ties() {
+        let assets = [asset("tiles-sounds-x64-a"), asset("tiles-sounds-x64-b")];
+        assert_eq!(x64().choose(&assets, None).unwrap().index, 0);
+    }
+
+    #[test]
+    fn follows_the_frontend_and_sounds_preference() {
+        let assets = [
+            asset("tiles-sounds-x64"),
+            asset("terminal-only-x64"),
+            asset("tiles-x64"),
+        ];
+        let curses = AssetPreference {
+            frontend: Some(Frontend::Curses),
+            ..x64()
+        };
+        assert_eq!(curses.choose(&assets, None).unwrap().index, 1);
+        let silent = AssetPreference {
+            sounds: Some(false),
+            ..x64()
+        };
+        assert_eq!(silent.choose(&assets, None).unwrap().index, 2);
+    }
+
+    #[test]
+    fn falls_back_to_a_compatible_arch() {
+        let assets = [asset("tiles-x32")];
+        let choice = x64().choose(&assets, None).unwrap();
+        assert_eq!(choice.index, 0);
+        assert!(
+            choice.reason.contains("no native build"),
+            "{}",
+            choice.reason
+        );
+
+        let x86 = AssetPreference {
+            arch: Some(Arch::X86),
+            ..Default::default()
+        };
+        assert!(x86.choose(&[asset("tiles-x64")], None).is_none());
+    }
+
+    #[test]
+    fn the_source_pattern_admits_unclassified_assets() {
+        let assets = [ClassifiedAsset::new(
+            "Cataclysm-Tiles.zip",
+            "https://example.com/Cataclysm-Tiles.zip",
+            None,
+            None,
+        )];
+        assert!(x64().choose(&assets, None).is_none());
+        let pattern = regex::Regex::new("^Cataclysm-Tiles").unwrap();
+        assert_eq!(x64().choose(&assets, Some(&pattern)).unwrap().index, 0);
+    }
+
+    #[test]
+    fn ignores_other_platforms() {
+        let other = if crate::get_platform() == "windows" {
+            "linux"
+        } else {
+            "windows"
+        };
+        let name = format!("cdda-{}-tiles-sounds-x64.zip", other);
+        let assets = [ClassifiedAsset::new(&name, &name, None, None)];
+        assert!(x64().choose(&assets, None).is_none());
+    }
+}
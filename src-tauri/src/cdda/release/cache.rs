@@ -0,0 +1,209 @@
+//! Persistent cache of release listings, so that browsing releases survives
+//! GitHub rate limits and network outages.
+use crate::prelude::*;
+use chrono::{DateTime, Duration, Utc};
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+const CACHE_FILENAME: &str = "releases.json";
+
+/// A value together with where it came from.
+#[derive(Debug, Clone)]
+pub struct Cached<T> {
+    pub value: T,
+    /// The value came from the cache. It may have been revalidated with the server in this call.
+    pub from_cache: bool,
+    /// The value is past its TTL and was served because the network request failed
+    /// (offline or rate-limited).
+    pub stale: bool,
+    pub fetched_at: DateTime<Utc>,
+}
+
+impl<T> Cached<T> {
+    pub fn fresh(value: T) -> Self {
+        Self {
+            value,
+            from_cache: false,
+            stale: false,
+            fetched_at: Utc::now(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+struct CacheEntry {
+    etag: Option<String>,
+    fetched_at: DateTime<Utc>,
+    body: serde_json::Value,
+}
+
+impl CacheEntry {
+    fn is_fresh(&self, ttl: Duration) -> bool {
+        Utc::now() - self.fetched_at < ttl
+    }
+
+    fn to_cached<T: DeserializeOwned>(&self, stale: bool) -> Result<Cached<T>> {
+        Ok(Cached {
+            value: serde_json::from_value(self.body.clone())?,
+            from_cache: true,
+            stale,
+            fetched_at: self.fetched_at,
+        })
+    }
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct CacheFile {
+    entries: HashMap<String, CacheEntry>,
+}
+
+fn read_file(path: &Path) -> Option<CacheFile> {
+    let s = std::fs::read_to_string(path).ok()?;
+    serde_json::from_str(&s)
+        .map_err(|e| warn!("Ignoring broken release cache: {}", e))
+        .ok()
+}
+
+fn cache_file() -> &'static std::sync::Mutex<CacheFile> {
+    static CACHE: OnceLock<std::sync::Mutex<CacheFile>> = OnceLock::new();
+    CACHE.get_or_init(|| {
+        let path = crate::paths::cache_dir().join(CACHE_FILENAME);
+        std::sync::Mutex::new(read_file(&path).unwrap_or_default())
+    })
+}
+
+/// Writes the cache, keeping the newer entries written meanwhile by another process (the app
+/// and the CLI share the file). The file is replaced by a rename, so it is never half-written.
+fn write_file(file: &mut CacheFile) -> Result<()> {
+    let dir = crate::paths::cache_dir();
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join(CACHE_FILENAME);
+    for (key, entry) in read_file(&path).unwrap_or_default().entries {
+        match file.entries.get(&key) {
+            Some(ours) if ours.fetched_at >= entry.fetched_at => {}
+            _ => {
+                file.entries.insert(key, entry);
+            }
+        }
+    }
+    let mut tmp = tempfile::NamedTempFile::new_in(&dir)?;
+    serde_json::to_writer(&mut tmp, &*file)?;
+    tmp.persist(&path)?;
+    Ok(())
+}
+
+fn get_entry(key: &str) -> Option<CacheEntry> {
+    cache_file().lock().unwrap().entries.get(key).cloned()
+}
+
+fn put_entry(key: &str, entry: CacheEntry) {
+    let mut file = cache_file().lock().unwrap();
+    file.entries.insert(key.to_string(), entry);
+    write_file(&mut file).unwrap_or_else(|e| warn!("Failed to write release cache: {}", e));
+}
+
+/// Returns the cached value of `key` if it is younger than `ttl`. Otherwise calls `fetch`,
+/// falling back to the stale value if it fails.
+pub fn get_or_fetch<T: Serialize + DeserializeOwned>(
+    key: &str,
+    ttl: Duration,
+    fetch: impl FnOnce() -> Result<T>,
+) -> Result<Cached<T>> {
+    let entry = get_entry(key);
+    if let Some(entry) = entry.as_ref().filter(|e| e.is_fresh(ttl)) {
+        debug!("Release cache hit: {}", key);
+        return entry.to_cached(false);
+    }
+    match fetch() {
+        Ok(value) => {
+            let body = serde_json::to_value(&value)?;
+            put_entry(
+                key,
+                CacheEntry {
+                    etag: None,
+                    fetched_at: Utc::now(),
+                    body,
+                },
+            );
+            Ok(Cached::fresh(value))
+        }
+        Err(e) => match entry {
+            Some(entry) => {
+                warn!("Serving stale cache for {}: {:#}", key, e);
+                entry.to_cached(true)
+            }
+            None => Err(e),
+        },
+    }
+}
+
+/// Returns the cached copy of `url` without sending a request, e.g. while the API quota is low.
+pub fn peek_json(url: &str, ttl: Duration) -> Option<Cached<serde_json::Value>> {
+    let entry = get_entry(url)?;
+    entry.to_cached(!entry.is_fresh(ttl)).ok()
+}
+
+/// GETs a JSON document, revalidating the cached copy with `If-None-Match` once it is older than `ttl`.
+/// Falls back to the cached copy when offline or rate-limited.
+///
+/// Returns `Ok(None)` for 404.
+pub fn get_json(
+    client: &reqwest::blocking::Client,
+    url: &str,
+    ttl: Duration,
+) -> Result<Option<Cached<serde_json::Value>>> {
+    let entry = get_entry(url);
+    if let Some(entry) = entry.as_ref().filter(|e| e.is_fresh(ttl)) {
+        debug!("Release cache hit: {}", url);
+        return entry.to_cached(false).map(Some);
+    }
+
+    let mut request = client.get(url);
+    if let Some(etag) = entry.as_ref().and_then(|e| e.etag.as_ref()) {
+        request = request.header(reqwest::header::IF_NONE_MATCH, etag);
+    }
+    let stale_or = |e: anyhow::Error| match &entry {
+        Some(entry) => {
+            warn!("Serving stale cache for {}: {:#}", url, e);
+            entry.to_cached(true).map(Some)
+        }
+        None => Err(e),
+    };
+    let response = match super::github::send(request) {
+        Ok(response) => response,
+        Err(e) => return stale_or(e.into()),
+    };
+
+    match response.status() {
+        reqwest::StatusCode::NOT_MODIFIED if entry.is_some() => {
+            debug!("Release cache revalidated: {}", url);
+            let mut entry = entry.unwrap();
+            entry.fetched_at = Utc::now();
+            put_entry(url, entry.clone());
+            entry.to_cached(false).map(Some)
+        }
+        reqwest::StatusCode::NOT_FOUND => Ok(None),
+        status if status.is_success() => {
+            let etag = response
+                .headers()
+                .get(reqwest::header::ETAG)
+                .and_then(|v| v.to_str().ok())
+                .map(|v| v.to_string());
+            let body: serde_json::Value = match response.json() {
+                Ok(body) => body,
+                Err(e) => return stale_or(e.into()),
+            };
+            put_entry(
+                url,
+                CacheEntry {
+                    etag,
+                    fetched_at: Utc::now(),
+                    body: body.clone(),
+                },
+            );
+            Ok(Some(Cached::fresh(body)))
+        }
+        status => stale_or(anyhow!("GET {} failed with {}", url, status)),
+    }
+}
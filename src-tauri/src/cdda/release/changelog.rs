@@ -0,0 +1,413 @@
+//! Changelog between two game builds, read from the local CDDA clone.
+//!
+//! The clone is shallow. The history between the two tags is fetched on demand, deepening the
+//! clone until the older tag is reached.
+use super::get_cdda_repo;
+use crate::git::sparse::SparseCheckout;
+use crate::git::{fetch_tags, shallow_commits};
+use crate::prelude::*;
+use chrono::{DateTime, TimeZone, Utc};
+use git2::{Delta, DiffOptions, Oid, Repository, Sort};
+use regex::Regex;
+use std::collections::BTreeMap;
+
+/// Depth of the first fetch. Deepened by `DEPTH_FACTOR` until the older tag is reached.
+const INITIAL_DEPTH: i32 = 256;
+const DEPTH_FACTOR: i32 = 4;
+/// Beyond this, the whole history is fetched.
+const MAX_DEPTH: i32 = 16384;
+/// libgit2's `GIT_FETCH_DEPTH_UNSHALLOW`
+const UNSHALLOW: i32 = i32::MAX;
+
+/// The changelog categories of CDDA pull requests, in the order they are listed.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeCategory {
+    Features,
+    Content,
+    Interface,
+    Mods,
+    Balance,
+    Bugfixes,
+    Performance,
+    Infrastructure,
+    Build,
+    I18n,
+    /// No recognised prefix.
+    Other,
+}
+
+impl ChangeCategory {
+    fn from_prefix(prefix: &str) -> Option<Self> {
+        let category = match prefix.trim().to_lowercase().as_str() {
+            "features" | "feature" => ChangeCategory::Features,
+            "content" => ChangeCategory::Content,
+            "interface" | "ui" => ChangeCategory::Interface,
+            "mods" | "mod" => ChangeCategory::Mods,
+            "balance" => ChangeCategory::Balance,
+            "bugfixes" | "bugfix" | "fix" => ChangeCategory::Bugfixes,
+            "performance" => ChangeCategory::Performance,
+            "infrastructure" => ChangeCategory::Infrastructure,
+            "build" => ChangeCategory::Build,
+            "i18n" => ChangeCategory::I18n,
+            _ => return None,
+        };
+        Some(category)
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ChangelogCommit {
+    pub sha: String,
+    /// The pull request title, or the commit summary for direct commits.
+    pub title: String,
+    pub pr_number: Option<u32>,
+    pub author: String,
+    pub time: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FileChange {
+    Added,
+    Modified,
+    Deleted,
+    Renamed,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ChangedFile {
+    /// Relative to the repository root, e.g. `data/json/items/tools.json`
+    pub path: String,
+    pub change: FileChange,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct Changelog {
+    pub from: String,
+    pub to: String,
+    pub groups: BTreeMap<ChangeCategory, Vec<ChangelogCommit>>,
+    /// Changed files under `data/json` and `data/mods`.
+    pub changed_files: Vec<ChangedFile>,
+}
+
+struct Patterns {
+    merge: Regex,
+    squash_pr: Regex,
+    prefix: Regex,
+    summary: Regex,
+}
+
+impl Patterns {
+    fn new() -> Self {
+        Self {
+            // e.g. "Merge pull request #12345 from user/branch"
+            merge: Regex::new(r"^Merge pull request #(?P<pr>\d+)").unwrap(),
+            // e.g. "Fix crash when reading books (#12345)"
+            squash_pr: Regex::new(r"\(#(?P<pr>\d+)\)\s*$").unwrap(),
+            // e.g. "[Mods] Add ...", "Bugfixes: Fix ...", "Balance - Tweak ..."
+            prefix: Regex::new(r"^\s*(?:\[(?P<bracket>[\w ]+)\]|(?P<plain>\w+)\s*[:\-])").unwrap(),
+            // The PR template: "#### Summary" followed by `Category "description"`
+            summary: Regex::new(r"(?m)^#+\s*Summary\s*\n+\s*(?P<category>\w+)\b").unwrap(),
+        }
+    }
+
+    /// Splits a first-parent commit into the PR number, title and category.
+    fn parse(&self, message: &str) -> (Option<u32>, String, ChangeCategory) {
+        let mut lines = message.lines().map(str::trim).filter(|l| !l.is_empty());
+        let summary = lines.next().unwrap_or_default();
+        let (pr_number, title) = match self.merge.captures(summary) {
+            // GitHub puts the PR title after the merge line.
+            Some(c) => (c["pr"].parse().ok(), lines.next().unwrap_or(summary)),
+            None => (
+                self.squash_pr
+                    .captures(summary)
+                    .and_then(|c| c["pr"].parse().ok()),
+                summary,
+            ),
+        };
+        let category = self
+            .prefix
+            .captures(title)
+            .and_then(|c| c.name("bracket").or(c.name("plain")))
+            .and_then(|m| ChangeCategory::from_prefix(m.as_str()))
+            .or_else(|| {
+                self.summary
+                    .captures(message)
+                    .and_then(|c| ChangeCategory::from_prefix(&c["category"]))
+            })
+            .unwrap_or(ChangeCategory::Other);
+        (pr_number, title.to_string(), category)
+    }
+}
+
+fn tag_commit(repo: &Repository, tag: &str) -> Result<Oid> {
+    let object = repo
+        .revparse_single(&format!("refs/tags/{}", tag))
+        .with_context(|| format!("Tag not found: {}", tag))?;
+    Ok(object.peel_to_commit()?.id())
+}
+
+/// First-parent commits after `from` up to `to`, newest first.
+/// Returns `None` if the shallow history does not reach `from`, and fails if `to` is older.
+fn first_parent_commits(repo: &Repository, from: Oid, to: Oid) -> Result<Option<Vec<Oid>>> {
+    if repo.merge_base(from, to).is_err() {
+        return Ok(None);
+    }
+    ensure!(
+        !repo.graph_descendant_of(from, to)?,
+        "The first build is newer than the second"
+    );
+    let shallow = shallow_commits(repo);
+    let mut walk = repo.revwalk()?;
+    walk.set_sorting(Sort::TOPOLOGICAL)?;
+    walk.simplify_first_parent()?;
+    walk.push(to)?;
+    walk.hide(from)?;
+    let mut commits = Vec::new();
+    for oid in walk {
+        let oid = oid?;
+        if shallow.contains(&oid) {
+            return Ok(None);
+        }
+        commits.push(oid);
+    }
+    Ok(Some(commits))
+}
+
+/// Fetches both tags and deepens the clone until the history between them is complete.
+fn fetch_history(repo: &Repository, from: &str, to: &str) -> Result<Vec<Oid>> {
+    let mut depth = INITIAL_DEPTH;
+    loop {
+        fetch_tags(repo, &[from, to], Some(depth))?;
+        let (from_oid, to_oid) = (tag_commit(repo, from)?, tag_commit(repo, to)?);
+        let commits = first_parent_commits(repo, from_oid, to_oid)
+            .with_context(|| format!("Changelog from {} to {}", from, to))?;
+        if let Some(commits) = commits {
+            return Ok(commits);
+        }
+        ensure!(depth != UNSHALLOW, "{} is not an ancestor of {}", from, to);
+        depth = if depth >= MAX_DEPTH {
+            UNSHALLOW
+        } else {
+            depth * DEPTH_FACTOR
+        };
+        info!(
+            "History between {} and {} is incomplete. Deepening to {}",
+            from, to, depth
+        );
+    }
+}
+
+fn changed_files(repo: &Repository, from: Oid, to: Oid) -> Result<Vec<ChangedFile>> {
+    let old_tree = repo.find_commit(from)?.tree()?;
+    let new_tree = repo.find_commit(to)?.tree()?;
+    let mut options = DiffOptions::new();
+    options.pathspec("data/json").pathspec("data/mods");
+    let mut diff = repo.diff_tree_to_tree(Some(&old_tree), Some(&new_tree), Some(&mut options))?;
+    diff.find_similar(None)?;
+    let mut files = diff
+        .deltas()
+        .filter_map(|delta| {
+            let change = match delta.status() {
+                Delta::Added | Delta::Copied => FileChange::Added,
+                Delta::Deleted => FileChange::Deleted,
+                Delta::Renamed => FileChange::Renamed,
+                Delta::Modified | Delta::Typechange => FileChange::Modified,
+                _ => return None,
+            };
+            let file = match change {
+                FileChange::Deleted => delta.old_file(),
+                _ => delta.new_file(),
+            };
+            Some(ChangedFile {
+                path: file.path()?.to_string_lossy().replace('\\', "/"),
+                change,
+            })
+        })
+        .collect::<Vec<_>>();
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(files)
+}
+
+/// Lists the pull requests merged after `from` up to `to`, and the game data they changed.
+/// `sparse` applies if the CDDA clone has to be created.
+pub fn changelog(from: &str, to: &str, sparse: &SparseCheckout) -> Result<Changelog> {
+    let repo = get_cdda_repo(sparse).map_err(|e| anyhow!(e))?;
+    let commits = fetch_history(&repo, from, to)?;
+    let patterns = Patterns::new();
+    let mut groups: BTreeMap<ChangeCategory, Vec<ChangelogCommit>> = BTreeMap::new();
+    for oid in commits {
+        let commit = repo.find_commit(oid)?;
+        let (pr_number, title, category) = patterns.parse(commit.message().unwrap_or_default());
+        let author = commit.author();
+        groups.entry(category).or_default().push(ChangelogCommit {
+            sha: oid.to_string(),
+            title,
+            pr_number,
+            author: author.name().unwrap_or_default().to_string(),
+            time: Utc
+                .timestamp_opt(author.when().seconds(), 0)
+                .single()
+                .unwrap_or_default(),
+        });
+    }
+    Ok(Changelog {
+        from: from.to_string(),
+        to: to.to_string(),
+        groups,
+        changed_files: changed_files(&repo, tag_commit(&repo, from)?, tag_commit(&repo, to)?)?,
+    })
+}
+
+pub mod commands {
+    use super::*;
+
+    /// Resolves an install id to the tag of its build. Anything else is taken as a tag.
+    fn resolve_tag(state: &AppState, tag_or_install: String) -> Result<String, String> {
+        let settings = state.get_settings().unwrap();
+        match settings.get_install(&tag_or_install) {
+            Some(install) => install
+                .tag
+                .clone()
+                .ok_or_else(|| format!("{} was not installed from a release", install.id)),
+            None => Ok(tag_or_install),
+        }
+    }
+
+    /// The changelog between two builds, given as tags or install ids.
+    /// The CDDA clone is deepened as needed, which may take a while the first time.
+    #[tauri::command]
+    pub async fn cdda_changelog(
+        state: tauri::State<'_, AppState>,
+        from: String,
+        to: String,
+    ) -> Result<Changelog, String> {
+        let from = resolve_tag(&state, from)?;
+        let to = resolve_tag(&state, to)?;
+        let sparse = state.get_settings().unwrap().cdda_sparse_checkout;
+        tauri::async_runtime::spawn_blocking(move || {
+            changelog(&from, &to, &sparse).map_err(|e| format!("{:#}", e))
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_merge_commits() {
+        let message = "Merge pull request #12345 from user/branch\n\n[Mods] Add a new mod";
+        assert_eq!(
+            Patterns::new().parse(message),
+            (
+                Some(12345),
+                "[Mods] Add a new mod".to_string(),
+                ChangeCategory::Mods
+            )
+        );
+    }
+
+    #[test]
+    fn parses_squashed_commits_and_prefixes() {
+        let patterns = Patterns::new();
+        let cases = [
+            (
+                "Bugfixes: Fix crash when reading books (#456)",
+                Some(456),
+                ChangeCategory::Bugfixes,
+            ),
+            (
+                "Balance - Tweak zombie speed (#457)",
+                Some(457),
+                ChangeCategory::Balance,
+            ),
+            (
+                "[UI] Show the item weight (#458)",
+                Some(458),
+                ChangeCategory::Interface,
+            ),
+            (
+                "[Unknown] Do something (#459)",
+                Some(459),
+                ChangeCategory::Other,
+            ),
+            (
+                "Fix crash when reading books (#460)",
+                Some(460),
+                ChangeCategory::Other,
+            ),
+            ("Update translations", None, ChangeCategory::Other),
+        ];
+        for (message, pr_number, category) in cases {
+            assert_eq!(
+                patterns.parse(message),
+                (pr_number, message.to_string(), category),
+                "{}",
+                message
+            );
+        }
+    }
+
+    #[test]
+    fn falls_back_to_the_summary_of_the_pr_template() {
+        let message = "Add new monsters (#789)\n\n#### Summary\nContent \"Add new monsters\"\n\n#### Purpose of change";
+        let (pr_number, title, category) = Patterns::new().parse(message);
+        assert_eq!(pr_number, Some(789));
+        assert_eq!(title, "Add new monsters (#789)");
+        assert_eq!(category, ChangeCategory::Content);
+    }
+
+    /// A linear history of `n` commits, oldest first.
+    fn linear_repo(dir: &Path, n: usize) -> (Repository, Vec<Oid>) {
+        let repo = Repository::init(dir).unwrap();
+        let commits = {
+            let signature = git2::Signature::now("test", "test@example.com").unwrap();
+            let tree_id = repo.index().unwrap().write_tree().unwrap();
+            let tree = repo.find_tree(tree_id).unwrap();
+            let mut commits: Vec<Oid> = Vec::new();
+            for i in 0..n {
+                let parent = commits.last().map(|oid| repo.find_commit(*oid).unwrap());
+                let parents = parent.iter().collect::<Vec<_>>();
+                let message = format!("Commit {}", i);
+                let oid = repo
+                    .commit(
+                        Some("HEAD"),
+                        &signature,
+                        &signature,
+                        &message,
+                        &tree,
+                        &parents,
+                    )
+                    .unwrap();
+                commits.push(oid);
+            }
+            commits
+        };
+        (repo, commits)
+    }
+
+    #[test]
+    fn lists_the_commits_between_two_builds() {
+        let dir = tempfile::tempdir().unwrap();
+        let (repo, commits) = linear_repo(dir.path(), 4);
+        let listed = first_parent_commits(&repo, commits[1], commits[3])
+            .unwrap()
+            .unwrap();
+        assert_eq!(listed, [commits[3], commits[2]]);
+        let same = first_parent_commits(&repo, commits[2], commits[2])
+            .unwrap()
+            .unwrap();
+        assert!(same.is_empty());
+    }
+
+    #[test]
+    fn rejects_swapped_builds() {
+        let dir = tempfile::tempdir().unwrap();
+        let (repo, commits) = linear_repo(dir.path(), 3);
+        assert!(first_parent_commits(&repo, commits[2], commits[0]).is_err());
+    }
+}
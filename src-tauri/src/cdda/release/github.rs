@@ -0,0 +1,208 @@
+//! GitHub API access: authentication and rate-limit bookkeeping.
+//!
+//! The quota reported in `X-RateLimit-*` headers is remembered per host, so that requests
+//! are deferred (served from the release cache) instead of failing once the quota runs out.
+use crate::prelude::*;
+use chrono::{DateTime, TimeZone, Utc};
+use reqwest::blocking::{RequestBuilder, Response};
+use std::collections::HashMap;
+use std::sync::{OnceLock, RwLock};
+
+pub const TOKEN_ENV: &str = "GITHUB_TOKEN";
+
+/// Below this many remaining requests, optional requests (e.g. per-tag lookups) are skipped.
+pub const LOW_QUOTA: u64 = 5;
+
+/// The request was not sent because the API quota is exhausted.
+#[derive(Debug, Clone)]
+pub struct RateLimited {
+    pub until: DateTime<Utc>,
+}
+
+impl std::fmt::Display for RateLimited {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "GitHub API rate limit exceeded. Try again after {}",
+            self.until
+                .with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M:%S")
+        )
+    }
+}
+
+impl std::error::Error for RateLimited {}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct RateLimit {
+    pub limit: Option<u64>,
+    pub remaining: u64,
+    pub reset: DateTime<Utc>,
+}
+
+impl RateLimit {
+    fn from_headers(headers: &reqwest::header::HeaderMap) -> Option<Self> {
+        let get = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.parse::<u64>().ok())
+        };
+        Some(Self {
+            limit: get("x-ratelimit-limit"),
+            remaining: get("x-ratelimit-remaining")?,
+            reset: Utc
+                .timestamp_opt(get("x-ratelimit-reset")? as i64, 0)
+                .single()?,
+        })
+    }
+
+    fn is_active(&self) -> bool {
+        self.reset > Utc::now()
+    }
+}
+
+fn token_store() -> &'static RwLock<Option<String>> {
+    static TOKEN: OnceLock<RwLock<Option<String>>> = OnceLock::new();
+    TOKEN.get_or_init(|| RwLock::new(None))
+}
+
+fn rate_limits() -> &'static RwLock<HashMap<String, RateLimit>> {
+    static LIMITS: OnceLock<RwLock<HashMap<String, RateLimit>>> = OnceLock::new();
+    LIMITS.get_or_init(|| RwLock::new(HashMap::new()))
+}
+
+/// Sets the personal access token from the settings. Falls back to `GITHUB_TOKEN` if `None`.
+pub fn set_token(token: Option<String>) {
+    *token_store().write().unwrap() = token.filter(|t| !t.trim().is_empty());
+}
+
+pub fn token() -> Option<String> {
+    token_store()
+        .read()
+        .unwrap()
+        .clone()
+        .or_else(|| std::env::var(TOKEN_ENV).ok().filter(|t| !t.is_empty()))
+}
+
+fn host_of(url: &reqwest::Url) -> String {
+    url.host_str().unwrap_or_default().to_string()
+}
+
+/// The last known quota of a host, if it has not been reset since.
+pub fn rate_limit(url: &str) -> Option<RateLimit> {
+    let host = reqwest::Url::parse(url).ok().map(|u| host_of(&u))?;
+    rate_limits()
+        .read()
+        .unwrap()
+        .get(&host)
+        .copied()
+        .filter(|r| r.is_active())
+}
+
+/// Whether optional requests to the host of `url` should be deferred.
+pub fn is_quota_low(url: &str) -> bool {
+    rate_limit(url).is_some_and(|r| r.remaining <= LOW_QUOTA)
+}
+
+fn retry_after(response: &Response) -> Option<DateTime<Utc>> {
+    let secs = response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .parse::<i64>()
+        .ok()?;
+    Some(Utc::now() + chrono::Duration::seconds(secs))
+}
+
+/// Sends a request, keeping track of the rate limit of the host.
+///
+/// Fails with `RateLimited` without sending if the quota is known to be exhausted,
+/// or if the server answers with a rate-limit error.
+pub fn send(request: RequestBuilder) -> Result<Response> {
+    let (client, request) = request.build_split();
+    let request = request?;
+    let host = host_of(request.url());
+    if let Some(limit) = rate_limits().read().unwrap().get(&host) {
+        if limit.is_active() && limit.remaining == 0 {
+            return Err(RateLimited { until: limit.reset }.into());
+        }
+    }
+
+    let response = client.execute(request)?;
+    let limit = RateLimit::from_headers(response.headers());
+    if let Some(limit) = limit {
+        debug!(
+            "Rate limit of {}: {}/{} until {}",
+            host,
+            limit.remaining,
+            limit.limit.unwrap_or_default(),
+            limit.reset
+        );
+        rate_limits().write().unwrap().insert(host.clone(), limit);
+    }
+
+    let status = response.status();
+    let limited = status == reqwest::StatusCode::TOO_MANY_REQUESTS
+        || (status == reqwest::StatusCode::FORBIDDEN && limit.is_some_and(|l| l.remaining == 0));
+    if limited {
+        let until = retry_after(&response)
+            .or(limit.map(|l| l.reset))
+            .unwrap_or_else(|| Utc::now() + chrono::Duration::minutes(1));
+        warn!("Rate limited by {} until {}", host, until);
+        return Err(RateLimited { until }.into());
+    }
+    Ok(response)
+}
+
+/// A client for the GitHub REST API, authenticated if a token is configured.
+pub fn api_client() -> reqwest::blocking::Client {
+    use reqwest::header::{HeaderMap, HeaderValue};
+    let mut headers = HeaderMap::new();
+    headers.insert("X-GitHub-Api-Version", "2022-11-28".parse().unwrap());
+    headers.insert("Accept", "application/vnd.github+json".parse().unwrap());
+    headers.insert("User-Agent", "catalyzer".parse().unwrap());
+    if let Some(token) = token() {
+        match HeaderValue::from_str(&format!("Bearer {}", token.trim())) {
+            Ok(mut value) => {
+                value.set_sensitive(true);
+                headers.insert(reqwest::header::AUTHORIZATION, value);
+            }
+            Err(_) => warn!("Ignoring malformed GitHub token"),
+        }
+    }
+    reqwest::blocking::Client::builder()
+        .default_headers(headers)
+        .build()
+        .unwrap()
+}
+
+/// Queries the quota of the API at `api_url`. This call does not count against the quota.
+pub fn fetch_rate_limit(api_url: &str) -> Result<RateLimit> {
+    let url = format!("{}/rate_limit", api_url);
+    if let Some(limit) = rate_limit(&url).filter(|l| l.remaining == 0) {
+        return Ok(limit);
+    }
+    let response = send(api_client().get(&url))?;
+    ensure!(
+        response.status().is_success(),
+        "Failed to get rate limit: {}",
+        response.status()
+    );
+    if let Some(limit) = RateLimit::from_headers(response.headers()) {
+        return Ok(limit);
+    }
+    let json: serde_json::Value = response.json().context("Invalid rate limit response")?;
+    let rate = &json["rate"];
+    Ok(RateLimit {
+        limit: rate["limit"].as_u64(),
+        remaining: rate["remaining"]
+            .as_u64()
+            .ok_or_else(|| anyhow!("Invalid rate limit response: {}", json))?,
+        reset: rate["reset"]
+            .as_i64()
+            .and_then(|t| Utc.timestamp_opt(t, 0).single())
+            .unwrap_or_else(Utc::now),
+    })
+}
@@ -0,0 +1,284 @@
+use crate::git::sparse::SparseCheckout;
+use crate::git::{git_clone, git_clone_sparse, pull_rebase};
+use crate::prelude::*;
+use asset::{AssetPreference, ClassifiedAsset};
+use cache::Cached;
+use chrono::Duration;
+use git2::Repository;
+use source::ReleaseSource;
+
+pub mod asset;
+pub mod cache;
+pub mod changelog;
+pub mod github;
+pub mod source;
+
+/// The repository cloned for browsing game data (see `cdda_is_cloned`).
+const BASE: &str = r#"CleverRaven/Cataclysm-DDA"#;
+
+/// New experimental builds are tagged several times a day.
+fn tags_ttl() -> Duration {
+    Duration::minutes(30)
+}
+
+/// Published releases rarely change. Stale entries are revalidated with ETag anyway.
+fn release_ttl() -> Duration {
+    Duration::hours(24)
+}
+
+fn shallow_clone_cdda(target_dir: PathBuf, sparse: &SparseCheckout) -> Result<Repository, String> {
+    let url = format!("https://github.com/{}.git", BASE);
+    let repo = if sparse.enabled {
+        git_clone_sparse(&url, &target_dir, sparse)
+    } else {
+        git_clone(&url, &target_dir, Some(true))
+    };
+    let repo = match repo {
+        Ok(repo) => repo,
+        Err(e) => {
+            return Err(format!("Failed to clone repository: {}", e));
+        }
+    };
+    debug!("CDDA Repository cloned with depth=1.");
+    Ok(repo)
+}
+
+/// Attempts to open the CDDA repository at the configured clone directory path.
+/// If the repository is not found, it will attempt to clone the repository from the
+/// configured base URL.
+///
+/// A new clone only checks out the `sparse` paths, if enabled.
+///
+/// Returns the opened or cloned repository, or an error message if the operation fails.
+fn get_cdda_repo(sparse: &SparseCheckout) -> Result<Repository, String> {
+    let target_dir = crate::paths::cdda_clone_dir();
+    let repo = match Repository::open(&target_dir) {
+        Ok(repo) => repo,
+        Err(_) => {
+            debug!("CDDA Repository not found. Will clone it.");
+            return shallow_clone_cdda(target_dir, sparse);
+        }
+    };
+    Ok(repo)
+}
+
+pub mod commands {
+    use super::*;
+    use crate::cdda::version::CddaVersion;
+    use crate::install::ReleaseChannel;
+    use serde::{Deserialize, Serialize};
+
+    /// Remaining GitHub API requests of the current quota.
+    #[tauri::command]
+    pub fn github_rate_limit() -> Result<u64, String> {
+        github::fetch_rate_limit("https://api.github.com")
+            .map(|limit| limit.remaining)
+            .map_err(|e| e.to_string())
+    }
+
+    #[tauri::command]
+    pub fn cdda_is_cloned() -> bool {
+        crate::paths::cdda_clone_dir().exists()
+    }
+
+    fn default_source_id() -> String {
+        source::DEFAULT_SOURCE_ID.to_string()
+    }
+
+    #[derive(Debug, Deserialize, Serialize, Clone)]
+    /// Represents information about a release of the CDDA project.
+    ///
+    /// This struct contains the tag name, browser URL, download URL and details of a release.
+    pub struct ReleaseInfo {
+        /// Id of the `ReleaseSource` the release belongs to.
+        #[serde(default = "default_source_id")]
+        pub source: String,
+        pub tag_name: String,
+        /// The version the tag names, if it is a CDDA version.
+        #[serde(default)]
+        pub version: Option<CddaVersion>,
+        pub browser_url: String,
+        pub download_url: String,
+        /// Size of the asset in bytes, if known.
+        #[serde(default)]
+        pub size: Option<u64>,
+        /// Digest of the asset (`sha256:<hex>`), if known.
+        #[serde(default)]
+        pub digest: Option<String>,
+        /// Download count of the asset, if known.
+        #[serde(default)]
+        pub download_count: Option<u64>,
+        /// When the release was published. Experimental builds fall back to the time in the tag.
+        #[serde(default)]
+        pub published_at: Option<chrono::DateTime<chrono::Utc>>,
+        /// Release notes (markdown).
+        #[serde(default)]
+        pub body: Option<String>,
+        #[serde(default)]
+        pub prerelease: bool,
+        /// Commit the tag points at.
+        #[serde(default)]
+        pub commit_sha: Option<String>,
+        /// Every asset of the release. The asset fields above describe the selected one.
+        #[serde(default)]
+        pub assets: Vec<ClassifiedAsset>,
+        /// Why the selected asset was chosen.
+        #[serde(default)]
+        pub selection_reason: Option<String>,
+    }
+
+    impl ReleaseInfo {
+        pub(super) fn use_asset(&mut self, index: usize, reason: String) {
+            let asset = &self.assets[index];
+            self.download_url = asset.download_url.clone();
+            self.size = asset.size;
+            self.digest = asset.digest.clone();
+            self.download_count = asset.download_count;
+            self.selection_reason = Some(reason);
+        }
+
+        /// Selects an asset by name instead of the default one.
+        pub fn select_asset(&mut self, name: &str) -> Result<()> {
+            let index = self
+                .assets
+                .iter()
+                .position(|a| a.name == name)
+                .ok_or_else(|| anyhow!("{} has no asset named {}", self.tag_name, name))?;
+            self.use_asset(index, format!("{}: selected by the user", name));
+            Ok(())
+        }
+    }
+
+    /// A release listing, and whether it was served from the release cache.
+    #[derive(Debug, Serialize, Clone)]
+    pub struct ReleaseList {
+        pub releases: Vec<ReleaseInfo>,
+        /// Some of the data came from the cache, possibly revalidated with the source in this call.
+        pub from_cache: bool,
+        /// Some of the data is outdated and was served because the source could not be reached
+        /// or the rate limit was exceeded.
+        pub stale: bool,
+        /// When the oldest part of the data was fetched.
+        pub fetched_at: chrono::DateTime<chrono::Utc>,
+    }
+
+    impl ReleaseList {
+        pub(super) fn new<T>(releases: Vec<ReleaseInfo>, tags: &Cached<T>) -> Self {
+            Self {
+                releases,
+                from_cache: tags.from_cache,
+                stale: tags.stale,
+                fetched_at: tags.fetched_at,
+            }
+        }
+
+        pub(super) fn merge<T>(&mut self, other: &Cached<T>) {
+            self.from_cache |= other.from_cache;
+            self.stale |= other.stale;
+            self.fetched_at = self.fetched_at.min(other.fetched_at);
+        }
+    }
+
+    fn find_source(
+        state: &tauri::State<'_, AppState>,
+        source: Option<String>,
+    ) -> Result<ReleaseSource, String> {
+        let settings = state.get_settings().unwrap();
+        settings
+            .release_source(source.as_deref())
+            .map_err(|e| e.to_string())
+    }
+
+    fn asset_preference(state: &tauri::State<'_, AppState>) -> AssetPreference {
+        state.get_settings().unwrap().asset_preference
+    }
+
+    /// Lists stable releases. `source` defaults to CDDA.
+    #[tauri::command]
+    pub fn cdda_get_stable_releases(
+        state: tauri::State<'_, AppState>,
+        num: usize,
+        source: Option<String>,
+    ) -> Result<ReleaseList, String> {
+        info!("retrieve stable releases.");
+        find_source(&state, source)?
+            .list(ReleaseChannel::Stable, num, &asset_preference(&state))
+            .map_err(|e| format!("Failed to get stable release tags: {}", e))
+    }
+
+    /// Lists experimental builds. `source` defaults to CDDA.
+    #[tauri::command]
+    pub fn cdda_get_latest_releases(
+        state: tauri::State<'_, AppState>,
+        num: usize,
+        source: Option<String>,
+    ) -> Result<ReleaseList, String> {
+        info!("retrieve latest releases.");
+        find_source(&state, source)?
+            .list(ReleaseChannel::Experimental, num, &asset_preference(&state))
+            .map_err(|e| format!("Failed to get latest release tags: {}", e))
+    }
+
+    #[tauri::command]
+    pub fn list_release_sources(state: tauri::State<'_, AppState>) -> Vec<ReleaseSource> {
+        state.get_settings().unwrap().release_sources
+    }
+
+    /// Replaces the configured release sources.
+    #[tauri::command]
+    pub fn set_release_sources(
+        state: tauri::State<'_, AppState>,
+        sources: Vec<ReleaseSource>,
+    ) -> Result<(), String> {
+        state.update_settings(|settings| settings.set_release_sources(sources))
+    }
+
+    #[tauri::command]
+    pub fn get_asset_preference(state: tauri::State<'_, AppState>) -> AssetPreference {
+        asset_preference(&state)
+    }
+
+    /// Sets which asset is selected by default in release listings.
+    #[tauri::command]
+    pub fn set_asset_preference(
+        state: tauri::State<'_, AppState>,
+        preference: AssetPreference,
+    ) -> Result<(), String> {
+        state.update_settings(|settings| {
+            settings.set_asset_preference(preference);
+            Ok(())
+        })
+    }
+
+    /// A full clone stays full, even with `cdda_sparse_checkout` enabled.
+    #[tauri::command]
+    pub fn cdda_pull_rebase(state: tauri::State<'_, AppState>) -> Result<(), String> {
+        let sparse = state.get_settings().unwrap().cdda_sparse_checkout;
+        let repo = get_cdda_repo(&sparse)?;
+        let keep_sparse = sparse.enabled && crate::git::sparse::is_sparse(&repo);
+        pull_rebase(&repo, keep_sparse.then_some(&sparse))
+    }
+
+    /// Changes the paths checked out in the CDDA clone, and applies them to an existing clone.
+    #[tauri::command]
+    pub async fn set_cdda_sparse_checkout(
+        app: tauri::AppHandle,
+        sparse: SparseCheckout,
+    ) -> Result<(), String> {
+        use tauri::Manager;
+        app.state::<AppState>().update_settings(|settings| {
+            settings.set_cdda_sparse_checkout(sparse.clone());
+            Ok(())
+        })?;
+        if !cdda_is_cloned() {
+            return Ok(());
+        }
+        tauri::async_runtime::spawn_blocking(move || {
+            let repo =
+                Repository::open(crate::paths::cdda_clone_dir()).map_err(|e| e.to_string())?;
+            sparse.apply(&repo).map_err(|e| format!("{:#}", e))
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+}
@@ -0,0 +1,621 @@
+//! Where releases are listed and downloaded from: a GitHub repository (CDDA itself, or a fork
+//! such as Bright Nights) or a plain HTTP mirror serving an index file.
+use super::asset::{AssetPreference, ClassifiedAsset};
+use super::cache::{self, Cached};
+use super::commands::{ReleaseInfo, ReleaseList};
+use super::github::{self, RateLimited};
+use super::{release_ttl, tags_ttl};
+use crate::cdda::version::cmp_tags;
+use crate::git::ls_remote_tag_commits;
+use crate::install::ReleaseChannel;
+use crate::prelude::*;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use regex::Regex;
+use std::collections::BTreeMap;
+
+pub const DEFAULT_SOURCE_ID: &str = "cdda";
+
+fn default_github_web_url() -> String {
+    "https://github.com".to_string()
+}
+
+fn default_github_api_url() -> String {
+    "https://api.github.com".to_string()
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SourceLocation {
+    /// Releases of a GitHub repository. Tags are listed with `git ls-remote`.
+    Github {
+        /// `owner/name`
+        repo: String,
+        #[serde(default = "default_github_web_url")]
+        web_url: String,
+        #[serde(default = "default_github_api_url")]
+        api_url: String,
+    },
+    /// A plain HTTP server serving a JSON index (see `MirrorIndex`).
+    Mirror { index_url: String },
+}
+
+/// A configured place to get game builds from.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ReleaseSource {
+    pub id: String,
+    pub name: String,
+    pub location: SourceLocation,
+    /// Regex matching the tags of stable releases.
+    pub stable_tag_pattern: String,
+    /// Regex matching the tags of experimental builds.
+    pub experimental_tag_pattern: String,
+    /// Regex matching asset names, per platform (`windows`, `macos`, `linux`). Only needed for assets
+    /// whose platform the classifier (see `asset::classify`) can't tell from the name.
+    pub asset_patterns: BTreeMap<String, String>,
+    /// Download URL templates of experimental builds per platform, for builds whose release can't be
+    /// fetched (offline, or while the API quota is low).
+    /// `{web_url}`, `{repo}`, `{tag}` and `{date}` (the part of the tag matched by `experimental_tag_pattern`)
+    /// are expanded. Platforms without a template are looked up through the API.
+    #[serde(default)]
+    pub experimental_url_templates: BTreeMap<String, String>,
+}
+
+/// The index file served by a mirror.
+///
+/// ```json
+/// { "releases": [ { "tag_name": "0.G", "published_at": "2023-03-01T00:00:00Z", "assets": [
+///     { "name": "cdda-windows-tiles-sounds-x64-msvc-0.G.zip", "url": "0.G/cdda-windows.zip",
+///       "size": 123, "digest": "sha256:..." } ] } ] }
+/// ```
+///
+/// Relative asset URLs are resolved against the index URL.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct MirrorIndex {
+    pub releases: Vec<MirrorRelease>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct MirrorRelease {
+    pub tag_name: String,
+    #[serde(default)]
+    pub browser_url: Option<String>,
+    #[serde(default)]
+    pub published_at: Option<DateTime<Utc>>,
+    /// Release notes.
+    #[serde(default)]
+    pub body: Option<String>,
+    #[serde(default)]
+    pub prerelease: bool,
+    #[serde(default)]
+    pub commit_sha: Option<String>,
+    #[serde(default)]
+    pub assets: Vec<MirrorAsset>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct MirrorAsset {
+    pub name: String,
+    pub url: String,
+    #[serde(default)]
+    pub size: Option<u64>,
+    #[serde(default)]
+    pub digest: Option<String>,
+    #[serde(default)]
+    pub download_count: Option<u64>,
+}
+
+/// Details of a release besides its assets.
+#[derive(Debug, Default)]
+struct ReleaseMeta {
+    published_at: Option<DateTime<Utc>>,
+    body: Option<String>,
+    prerelease: bool,
+    commit_sha: Option<String>,
+}
+
+impl ReleaseMeta {
+    fn from_github(json: &serde_json::Value) -> Self {
+        Self {
+            published_at: json["published_at"]
+                .as_str()
+                .and_then(|s| DateTime::parse_from_rfc3339(s).ok())
+                .map(|t| t.with_timezone(&Utc)),
+            body: json["body"]
+                .as_str()
+                .filter(|s| !s.trim().is_empty())
+                .map(|s| s.to_string()),
+            prerelease: json["prerelease"].as_bool().unwrap_or(false),
+            // A branch name, unless the release was created from a commit.
+            commit_sha: json["target_commitish"]
+                .as_str()
+                .filter(|s| s.len() == 40 && s.chars().all(|c| c.is_ascii_hexdigit()))
+                .map(|s| s.to_string()),
+        }
+    }
+}
+
+/// All assets of a GitHub release JSON.
+fn github_assets(json: &serde_json::Value) -> Vec<ClassifiedAsset> {
+    json["assets"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|asset| {
+            Some(ClassifiedAsset {
+                download_count: asset["download_count"].as_u64(),
+                ..ClassifiedAsset::new(
+                    asset["name"].as_str()?,
+                    asset["browser_download_url"].as_str()?,
+                    asset["size"].as_u64(),
+                    asset["digest"].as_str().map(|s| s.to_string()),
+                )
+            })
+        })
+        .collect()
+}
+
+pub fn default_release_sources() -> Vec<ReleaseSource> {
+    vec![ReleaseSource::cdda()]
+}
+
+impl ReleaseSource {
+    /// CleverRaven/Cataclysm-DDA
+    pub fn cdda() -> Self {
+        let release_download = "{web_url}/{repo}/releases/download/{tag}";
+        Self {
+            id: DEFAULT_SOURCE_ID.to_string(),
+            name: "Cataclysm: Dark Days Ahead".to_string(),
+            location: SourceLocation::Github {
+                repo: "CleverRaven/Cataclysm-DDA".to_string(),
+                web_url: default_github_web_url(),
+                api_url: default_github_api_url(),
+            },
+            stable_tag_pattern: r"^0\.[A-Z](?:-(?:\d+|RELEASE))?$".to_string(),
+            experimental_tag_pattern: r"\d{4}-\d{2}-\d{2}-\d{4}".to_string(),
+            asset_patterns: BTreeMap::from([
+                (
+                    "windows".to_string(),
+                    r"windows-tiles-sounds-x64|Windows_x64".to_string(),
+                ),
+                ("macos".to_string(), r"osx-tiles|OSX-Tiles".to_string()),
+                (
+                    "linux".to_string(),
+                    r"linux-with-graphics-x64|linux-tiles-x64|Linux_x64-Tiles".to_string(),
+                ),
+            ]),
+            experimental_url_templates: BTreeMap::from([
+                (
+                    "windows".to_string(),
+                    format!(
+                        "{}/cdda-windows-tiles-sounds-x64-msvc-{{date}}.zip",
+                        release_download
+                    ),
+                ),
+                (
+                    "macos".to_string(),
+                    format!("{}/cdda-osx-tiles-universal-{{date}}.dmg", release_download),
+                ),
+                (
+                    "linux".to_string(),
+                    format!(
+                        "{}/cdda-linux-with-graphics-x64-{{date}}.tar.gz",
+                        release_download
+                    ),
+                ),
+            ]),
+        }
+    }
+
+    /// Checks that the patterns are valid regexes.
+    pub fn validate(&self) -> Result<()> {
+        ensure!(!self.id.is_empty(), "Source id is empty");
+        let patterns = [&self.stable_tag_pattern, &self.experimental_tag_pattern]
+            .into_iter()
+            .chain(self.asset_patterns.values());
+        for pattern in patterns {
+            Regex::new(pattern)
+                .with_context(|| format!("Invalid pattern in source {}: {}", self.id, pattern))?;
+        }
+        Ok(())
+    }
+
+    fn tag_pattern(&self, channel: ReleaseChannel) -> Result<Regex> {
+        let pattern = match channel {
+            ReleaseChannel::Stable => &self.stable_tag_pattern,
+            ReleaseChannel::Experimental => &self.experimental_tag_pattern,
+            ReleaseChannel::Local => return Err(anyhow!("Local builds have no releases")),
+        };
+        Ok(Regex::new(pattern)?)
+    }
+
+    pub fn channel_of(&self, tag_name: &str) -> Option<ReleaseChannel> {
+        [ReleaseChannel::Stable, ReleaseChannel::Experimental]
+            .into_iter()
+            .find(|channel| {
+                self.tag_pattern(*channel)
+                    .map(|re| re.is_match(tag_name))
+                    .unwrap_or(false)
+            })
+    }
+
+    /// Regex admitting assets of this platform whose names the classifier can't read.
+    fn asset_pattern(&self) -> Option<Regex> {
+        let pattern = self.asset_patterns.get(&crate::get_platform())?;
+        Regex::new(pattern)
+            .map_err(|e| warn!("Invalid asset pattern of {}: {}", self.id, e))
+            .ok()
+    }
+
+    fn browser_url(&self, tag_name: &str) -> String {
+        match &self.location {
+            SourceLocation::Github { repo, web_url, .. } => {
+                format!("{}/{}/releases/tag/{}", web_url, repo, tag_name)
+            }
+            SourceLocation::Mirror { index_url } => index_url.clone(),
+        }
+    }
+
+    /// Tags of the source, with the commit each points at if known.
+    fn tags(&self) -> Result<Cached<BTreeMap<String, Option<String>>>> {
+        match &self.location {
+            SourceLocation::Github { repo, web_url, .. } => {
+                let url = format!("{}/{}.git", web_url, repo);
+                let tags =
+                    cache::get_or_fetch(&format!("tag_commits:{}", url), tags_ttl(), || {
+                        ls_remote_tag_commits(url.clone())
+                    })?;
+                Ok(Cached {
+                    value: tags
+                        .value
+                        .into_iter()
+                        .map(|(tag, sha)| (tag, Some(sha)))
+                        .collect(),
+                    from_cache: tags.from_cache,
+                    stale: tags.stale,
+                    fetched_at: tags.fetched_at,
+                })
+            }
+            SourceLocation::Mirror { .. } => {
+                let index = self.mirror_index()?;
+                Ok(Cached {
+                    value: index
+                        .value
+                        .releases
+                        .into_iter()
+                        .map(|r| (r.tag_name, r.commit_sha))
+                        .collect(),
+                    from_cache: index.from_cache,
+                    stale: index.stale,
+                    fetched_at: index.fetched_at,
+                })
+            }
+        }
+    }
+
+    fn mirror_index(&self) -> Result<Cached<MirrorIndex>> {
+        let SourceLocation::Mirror { index_url } = &self.location else {
+            return Err(anyhow!("{} is not a mirror", self.id));
+        };
+        let client = reqwest::blocking::Client::builder()
+            .user_agent("catalyzer")
+            .build()?;
+        let json = cache::get_json(&client, index_url, tags_ttl())?
+            .ok_or_else(|| anyhow!("Mirror index not found: {}", index_url))?;
+        Ok(Cached {
+            value: serde_json::from_value(json.value).context("Invalid mirror index")?,
+            from_cache: json.from_cache,
+            stale: json.stale,
+            fetched_at: json.fetched_at,
+        })
+    }
+
+    /// GETs a GitHub API endpoint through the release cache.
+    /// While the quota is low, only the cached copy is used.
+    fn github_api_json(&self, path: &str) -> Result<Option<Cached<serde_json::Value>>> {
+        let SourceLocation::Github { api_url, .. } = &self.location else {
+            return Err(anyhow!("{} is not a GitHub source", self.id));
+        };
+        let endpoint = format!("{}{}", api_url, path);
+        if github::is_quota_low(&endpoint) {
+            debug!("API quota is low. Deferred: {}", endpoint);
+            return Ok(cache::peek_json(&endpoint, release_ttl()));
+        }
+        debug!("Getting release info from {}", endpoint);
+        cache::get_json(&github::api_client(), &endpoint, release_ttl())
+    }
+
+    /// Gets the release JSON of a tag.
+    fn github_release_json(&self, tag_name: &str) -> Result<Option<Cached<serde_json::Value>>> {
+        let SourceLocation::Github { repo, .. } = &self.location else {
+            return Err(anyhow!("{} is not a GitHub source", self.id));
+        };
+        self.github_api_json(&format!("/repos/{}/releases/tags/{}", repo, tag_name))
+    }
+
+    /// Gets the latest releases in a single request, so that listing does not need one request per tag.
+    fn github_releases_page(&self) -> Result<Option<Cached<serde_json::Value>>> {
+        let SourceLocation::Github { repo, .. } = &self.location else {
+            return Err(anyhow!("{} is not a GitHub source", self.id));
+        };
+        self.github_api_json(&format!("/repos/{}/releases?per_page=100", repo))
+    }
+
+    fn needs_api(&self, tag_name: &str) -> bool {
+        self.channel_of(tag_name) != Some(ReleaseChannel::Experimental)
+            || self.infer_experimental_asset(tag_name).is_none()
+    }
+
+    /// The asset named by the experimental URL template of this platform, if any.
+    fn infer_experimental_asset(&self, tag_name: &str) -> Option<ClassifiedAsset> {
+        let SourceLocation::Github { repo, web_url, .. } = &self.location else {
+            return None;
+        };
+        let template = self
+            .experimental_url_templates
+            .get(&crate::get_platform())?;
+        let date = self
+            .tag_pattern(ReleaseChannel::Experimental)
+            .ok()?
+            .find(tag_name)?
+            .as_str()
+            .to_string();
+        let url = template
+            .replace("{web_url}", web_url)
+            .replace("{repo}", repo)
+            .replace("{tag}", tag_name)
+            .replace("{date}", &date);
+        let name = url.rsplit('/').next().unwrap_or_default().to_string();
+        Some(ClassifiedAsset::new(&name, &url, None, None))
+    }
+
+    /// The build time in an experimental tag, for releases without a publish date.
+    fn experimental_tag_time(&self, tag_name: &str) -> Option<DateTime<Utc>> {
+        let date = self
+            .tag_pattern(ReleaseChannel::Experimental)
+            .ok()?
+            .find(tag_name)?;
+        NaiveDateTime::parse_from_str(date.as_str(), "%Y-%m-%d-%H%M")
+            .ok()
+            .map(|t| t.and_utc())
+    }
+
+    /// Builds the release info, picking the default asset according to `preference`.
+    fn release_info(
+        &self,
+        tag_name: &str,
+        meta: ReleaseMeta,
+        assets: Vec<ClassifiedAsset>,
+        preference: &AssetPreference,
+    ) -> ReleaseInfo {
+        let choice = preference.choose(&assets, self.asset_pattern().as_ref());
+        if choice.is_none() {
+            warn!(
+                "No asset found for {} among {} assets of {}",
+                crate::get_platform(),
+                assets.len(),
+                tag_name
+            );
+        }
+        let mut info = ReleaseInfo {
+            source: self.id.clone(),
+            tag_name: tag_name.to_string(),
+            version: tag_name.parse().ok(),
+            browser_url: self.browser_url(tag_name),
+            download_url: String::new(),
+            size: None,
+            digest: None,
+            download_count: None,
+            published_at: meta
+                .published_at
+                .or_else(|| self.experimental_tag_time(tag_name)),
+            body: meta.body,
+            prerelease: meta.prerelease,
+            commit_sha: meta.commit_sha,
+            assets,
+            selection_reason: None,
+        };
+        if let Some(choice) = choice {
+            info.use_asset(choice.index, choice.reason);
+        }
+        info
+    }
+
+    /// Looks up the assets of one GitHub release, recording cache use in `list`.
+    /// `page` is the batch fetched by `github_releases_page`, if any.
+    fn github_release(
+        &self,
+        tag_name: &str,
+        page: Option<&serde_json::Value>,
+        list: &mut ReleaseList,
+        preference: &AssetPreference,
+    ) -> ReleaseInfo {
+        let in_page = page.and_then(|page| page.as_array()).and_then(|releases| {
+            releases
+                .iter()
+                .find(|r| r["tag_name"].as_str() == Some(tag_name))
+        });
+        if let Some(json) = in_page {
+            let (meta, assets) = (ReleaseMeta::from_github(json), github_assets(json));
+            return self.release_info(tag_name, meta, assets, preference);
+        }
+        // Older than the batch. Fetched once per tag, then served from the release cache.
+        let json = self.github_release_json(tag_name).unwrap_or_else(|e| {
+            warn!("Failed to get release info of {}: {:#}", tag_name, e);
+            None
+        });
+        let (meta, assets) = match json {
+            Some(json) => {
+                list.merge(&json);
+                (
+                    ReleaseMeta::from_github(&json.value),
+                    github_assets(&json.value),
+                )
+            }
+            // Only the default asset is known then.
+            None => (
+                ReleaseMeta::default(),
+                self.infer_experimental_asset(tag_name)
+                    .into_iter()
+                    .collect(),
+            ),
+        };
+        self.release_info(tag_name, meta, assets, preference)
+    }
+
+    fn mirror_releases(
+        &self,
+        index: &MirrorIndex,
+        tags: &[String],
+        preference: &AssetPreference,
+    ) -> Vec<ReleaseInfo> {
+        let SourceLocation::Mirror { index_url } = &self.location else {
+            return Vec::new();
+        };
+        let base = reqwest::Url::parse(index_url).ok();
+        tags.iter()
+            .filter_map(|tag| index.releases.iter().find(|r| &r.tag_name == tag))
+            .map(|release| {
+                let assets = release
+                    .assets
+                    .iter()
+                    .map(|a| {
+                        let url = base
+                            .as_ref()
+                            .and_then(|base| base.join(&a.url).ok())
+                            .map(|url| url.to_string())
+                            .unwrap_or_else(|| a.url.clone());
+                        ClassifiedAsset {
+                            download_count: a.download_count,
+                            ..ClassifiedAsset::new(&a.name, &url, a.size, a.digest.clone())
+                        }
+                    })
+                    .collect();
+                let meta = ReleaseMeta {
+                    published_at: release.published_at,
+                    body: release.body.clone(),
+                    prerelease: release.prerelease,
+                    commit_sha: release.commit_sha.clone(),
+                };
+                let mut info = self.release_info(&release.tag_name, meta, assets, preference);
+                if let Some(browser_url) = &release.browser_url {
+                    info.browser_url = browser_url.clone();
+                }
+                info
+            })
+            .collect()
+    }
+
+    /// Lists the newest `num` releases of a channel with all their assets.
+    pub fn list(
+        &self,
+        channel: ReleaseChannel,
+        num: usize,
+        preference: &AssetPreference,
+    ) -> Result<ReleaseList> {
+        let tags = self.tags()?;
+        let re = self.tag_pattern(channel)?;
+        let mut matched = tags
+            .value
+            .keys()
+            .filter(|tag| re.is_match(tag))
+            .cloned()
+            .collect::<Vec<String>>();
+        // Newest first
+        matched.sort_unstable_by(|a, b| cmp_tags(b, a));
+        matched.truncate(num);
+        debug!("{:?}", matched);
+
+        let mut list = ReleaseList::new(Vec::new(), &tags);
+        match &self.location {
+            SourceLocation::Github { .. } => {
+                // One request lists the assets of all recent releases.
+                // URL templates stand in for experimental builds if it fails.
+                let page = match self.github_releases_page() {
+                    Ok(page) => page,
+                    // Nothing to show without the API. Report when to retry.
+                    Err(e)
+                        if e.is::<RateLimited>()
+                            && matched.iter().any(|tag| self.needs_api(tag)) =>
+                    {
+                        return Err(e)
+                    }
+                    Err(e) => {
+                        warn!("Failed to get releases of {}: {:#}", self.id, e);
+                        None
+                    }
+                };
+                if let Some(page) = &page {
+                    list.merge(page);
+                }
+                for tag in &matched {
+                    let info = self.github_release(
+                        tag,
+                        page.as_ref().map(|p| &p.value),
+                        &mut list,
+                        preference,
+                    );
+                    list.releases.push(info);
+                }
+            }
+            SourceLocation::Mirror { .. } => {
+                let index = self.mirror_index()?;
+                list.merge(&index);
+                list.releases = self.mirror_releases(&index.value, &matched, preference);
+            }
+        }
+        for info in &mut list.releases {
+            if info.commit_sha.is_none() {
+                info.commit_sha = tags.value.get(&info.tag_name).cloned().flatten();
+            }
+        }
+        Ok(list)
+    }
+
+    /// Builds the release info of a single tag.
+    pub fn get_release_info(
+        &self,
+        tag_name: &str,
+        preference: &AssetPreference,
+    ) -> Result<ReleaseInfo> {
+        let info = match &self.location {
+            SourceLocation::Github { .. } => {
+                let json = self.github_release_json(tag_name).unwrap_or_else(|e| {
+                    warn!("Failed to get release info of {}: {:#}", tag_name, e);
+                    None
+                });
+                let (meta, assets) = match json {
+                    Some(json) => (
+                        ReleaseMeta::from_github(&json.value),
+                        github_assets(&json.value),
+                    ),
+                    None => (
+                        ReleaseMeta::default(),
+                        self.infer_experimental_asset(tag_name)
+                            .into_iter()
+                            .collect(),
+                    ),
+                };
+                let mut info = self.release_info(tag_name, meta, assets, preference);
+                if info.commit_sha.is_none() {
+                    info.commit_sha = self
+                        .tags()
+                        .ok()
+                        .and_then(|tags| tags.value.get(tag_name).cloned().flatten());
+                }
+                info
+            }
+            SourceLocation::Mirror { .. } => {
+                let index = self.mirror_index()?;
+                self.mirror_releases(&index.value, &[tag_name.to_string()], preference)
+                    .pop()
+                    .ok_or_else(|| anyhow!("Release not found: {}", tag_name))?
+            }
+        };
+        ensure!(
+            !info.download_url.is_empty(),
+            "No downloadable asset found for {}",
+            tag_name
+        );
+        Ok(info)
+    }
+}
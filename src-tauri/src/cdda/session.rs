@@ -0,0 +1,297 @@
+use crate::cdda::crash_report::create_crash_report;
+use crate::cdda::hooks::run_post_exit;
+use crate::prelude::*;
+use crate::profile::Profile;
+use chrono::{DateTime, Local};
+use std::collections::HashMap;
+use std::process::{Child, ExitStatus};
+use tauri::{AppHandle, Manager};
+
+/// Event emitted when the game process has started. Payload: [`GameSession`]
+pub const GAME_STARTED: &str = "GAME_STARTED";
+/// Event emitted when the game process has exited. Payload: [`GameSession`]
+pub const GAME_EXITED: &str = "GAME_EXITED";
+
+/// The game build a profile plays: its install id, or its game path if it does not reference
+/// an install.
+pub fn game_build(profile: &Profile) -> Option<String> {
+    profile.get_install_id().map(str::to_string).or_else(|| {
+        profile
+            .get_game_path()
+            .map(|path| path.display().to_string())
+    })
+}
+
+/// A record of a single game launch.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct GameSession {
+    pub id: String,
+    pub profile_id: String,
+    /// The game build used for this session: the install id of the profile, or its game path
+    /// if the profile does not reference an install.
+    pub game_build: Option<String>,
+    pub pid: u32,
+    pub started_at: DateTime<Local>,
+    pub ended_at: Option<DateTime<Local>>,
+    /// `None` while running, or if the process was terminated by a signal.
+    pub exit_code: Option<i32>,
+    pub success: Option<bool>,
+    pub play_time_secs: Option<i64>,
+    /// Crash report bundle created when the game exited abnormally.
+    #[serde(default)]
+    pub crash_report: Option<PathBuf>,
+}
+
+impl GameSession {
+    pub fn new(profile: &Profile, pid: u32) -> Self {
+        let started_at = Local::now();
+        Self {
+            id: format!("{}_{}", started_at.format("%Y%m%d%H%M%S"), pid),
+            profile_id: profile.get_id().to_string(),
+            game_build: game_build(profile),
+            pid,
+            started_at,
+            ended_at: None,
+            exit_code: None,
+            success: None,
+            play_time_secs: None,
+            crash_report: None,
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.ended_at.is_none()
+    }
+
+    fn finish(&mut self, status: Option<ExitStatus>) {
+        let ended_at = Local::now();
+        self.play_time_secs = Some((ended_at - self.started_at).num_seconds());
+        self.ended_at = Some(ended_at);
+        self.exit_code = status.and_then(|s| s.code());
+        self.success = Some(status.is_some_and(|s| s.success()));
+    }
+}
+
+fn sessions_file(profile_id: &str) -> PathBuf {
+    crate::paths::session_dir().join(format!("{}.yaml", profile_id))
+}
+
+/// Returns the recorded sessions of a profile, oldest first.
+pub fn load_sessions(profile_id: &str) -> Vec<GameSession> {
+    let file = sessions_file(profile_id);
+    if !file.exists() {
+        return Vec::new();
+    }
+    std::fs::read_to_string(&file)
+        .map_err(anyhow::Error::from)
+        .and_then(|input| serde_yaml::from_str(&input).map_err(anyhow::Error::from))
+        .unwrap_or_else(|e| {
+            warn!("Failed to read sessions file {:?}: {}", file, e);
+            Vec::new()
+        })
+}
+
+/// Inserts or updates (by id) a session in the profile's session file.
+pub fn save_session(session: &GameSession) -> Result<()> {
+    let mut sessions = load_sessions(&session.profile_id);
+    match sessions.iter_mut().find(|s| s.id == session.id) {
+        Some(s) => *s = session.clone(),
+        None => sessions.push(session.clone()),
+    }
+    let file = sessions_file(&session.profile_id);
+    std::fs::create_dir_all(file.parent().unwrap())?;
+    std::fs::write(&file, serde_yaml::to_string(&sessions)?)
+        .with_context(|| format!("Failed to write sessions file {:?}", file))
+}
+
+/// Deletes the recorded sessions of a profile.
+pub fn remove_sessions(profile_id: &str) {
+    let file = sessions_file(profile_id);
+    if file.exists() {
+        std::fs::remove_file(&file)
+            .unwrap_or_else(|e| warn!("Failed to remove sessions file {:?}: {}", file, e));
+    }
+}
+
+/// Records the start of a game session.
+pub fn start_session(profile: &Profile, child: &Child) -> GameSession {
+    let session = GameSession::new(profile, child.id());
+    save_session(&session).unwrap_or_else(|e| warn!("{}", e));
+    info!("Game started. session: {}", session.id);
+    session
+}
+
+/// Waits for the game process to exit and records the result.
+/// If the game exited abnormally, a crash report is bundled.
+pub fn wait_session(profile: &Profile, mut session: GameSession, mut child: Child) -> GameSession {
+    let status = child
+        .wait()
+        .map_err(|e| warn!("Failed to wait for the game process: {}", e))
+        .ok();
+    session.finish(status);
+    if session.success != Some(true) {
+        session.crash_report = create_crash_report(profile, &session)
+            .map_err(|e| warn!("Failed to create crash report: {}", e))
+            .ok();
+    }
+    run_post_exit(profile, &session);
+    save_session(&session).unwrap_or_else(|e| warn!("{}", e));
+    info!(
+        "Game exited. session: {}, exit code: {:?}, play time: {:?}s",
+        session.id, session.exit_code, session.play_time_secs
+    );
+    session
+}
+
+/// Sessions currently running, keyed by profile id. A profile runs one game at a time.
+#[derive(Debug, Default)]
+pub struct SessionState {
+    active: std::sync::Mutex<HashMap<String, GameSession>>,
+}
+
+impl SessionState {
+    pub fn get_active(&self, profile_id: &str) -> Option<GameSession> {
+        self.active.lock().unwrap().get(profile_id).cloned()
+    }
+
+    pub fn is_any_active(&self) -> bool {
+        !self.active.lock().unwrap().is_empty()
+    }
+
+    /// Fails if the game of the profile is running, so that its build is not switched under it.
+    pub fn ensure_not_running(&self, profile_id: &str) -> Result<()> {
+        ensure!(
+            self.get_active(profile_id).is_none(),
+            "Cannot switch the game build while the game is running"
+        );
+        Ok(())
+    }
+}
+
+/// Records the session and watches the game process on a background thread.
+/// Emits [`GAME_STARTED`] now and [`GAME_EXITED`] when the game exits.
+pub fn track(app: AppHandle, profile: &Profile, child: Child) -> GameSession {
+    let session = start_session(profile, &child);
+    app.state::<SessionState>()
+        .active
+        .lock()
+        .unwrap()
+        .insert(session.profile_id.clone(), session.clone());
+    app.emit(GAME_STARTED, &session)
+        .unwrap_or_else(|e| warn!("{}", e));
+
+    let started = session.clone();
+    let profile = profile.clone();
+    std::thread::spawn(move || {
+        let session = wait_session(&profile, started, child);
+        let rolled_back = app
+            .state::<AppState>()
+            .update_settings(|settings| {
+                Ok(crate::install::rollback::on_session_end(settings, &session))
+            })
+            .unwrap_or_default();
+        if let Some(build) = rolled_back {
+            let payload = crate::install::rollback::RolledBack {
+                profile_id: session.profile_id.clone(),
+                build,
+            };
+            app.emit(crate::install::rollback::ROLLED_BACK, &payload)
+                .unwrap_or_else(|e| warn!("{}", e));
+        }
+        let sessions = app.state::<SessionState>();
+        let mut active = sessions.active.lock().unwrap();
+        // Keep the entry of another game of the profile, in case one was launched anyway.
+        if active
+            .get(&session.profile_id)
+            .is_some_and(|s| s.id == session.id)
+        {
+            active.remove(&session.profile_id);
+        }
+        drop(active);
+        app.emit(GAME_EXITED, &session)
+            .unwrap_or_else(|e| warn!("{}", e));
+    });
+    session
+}
+
+pub mod commands {
+    use super::*;
+
+    fn resolve_profile_id(state: &AppState, profile_id: Option<String>) -> String {
+        profile_id.unwrap_or_else(|| {
+            let settings = state.get_settings().unwrap();
+            settings.get_active_profile().get_id().to_string()
+        })
+    }
+
+    /// Returns the running session of the profile (the active profile if omitted), if any.
+    #[tauri::command]
+    pub fn get_active_session(
+        state: tauri::State<'_, AppState>,
+        sessions: tauri::State<'_, SessionState>,
+        profile_id: Option<String>,
+    ) -> Option<GameSession> {
+        let profile_id = resolve_profile_id(&state, profile_id);
+        sessions.get_active(&profile_id)
+    }
+
+    /// Returns the past sessions of the profile (the active profile if omitted), newest first.
+    #[tauri::command]
+    pub fn list_sessions(
+        state: tauri::State<'_, AppState>,
+        profile_id: Option<String>,
+    ) -> Vec<GameSession> {
+        let profile_id = resolve_profile_id(&state, profile_id);
+        let mut sessions = load_sessions(&profile_id);
+        sessions.reverse();
+        sessions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile(id: &str) -> Profile {
+        crate::paths::use_temp_dirs();
+        Profile::new(id.to_string(), "Session test".to_string(), None)
+    }
+
+    #[test]
+    fn saves_sessions_by_id() {
+        let profile = profile("saves_sessions");
+        let first = GameSession::new(&profile, 1);
+        let mut second = GameSession::new(&profile, 2);
+        save_session(&first).unwrap();
+        save_session(&second).unwrap();
+        second.finish(None);
+        save_session(&second).unwrap();
+
+        let sessions = load_sessions("saves_sessions");
+        let ids = sessions.iter().map(|s| s.id.as_str()).collect::<Vec<_>>();
+        assert_eq!(ids, [first.id.as_str(), second.id.as_str()]);
+        assert!(sessions[0].is_running());
+        assert!(!sessions[1].is_running());
+        assert_eq!(sessions[1].success, Some(false));
+        assert_eq!(sessions[1].exit_code, None);
+
+        remove_sessions("saves_sessions");
+        assert!(load_sessions("saves_sessions").is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn records_the_exit_status() {
+        use std::os::unix::process::ExitStatusExt;
+        let profile = profile("exit_status");
+        let mut exited = GameSession::new(&profile, 3);
+        exited.finish(Some(ExitStatus::from_raw(0)));
+        assert_eq!((exited.exit_code, exited.success), (Some(0), Some(true)));
+        assert!(exited.play_time_secs.is_some());
+
+        // Killed by SIGSEGV
+        let mut crashed = GameSession::new(&profile, 4);
+        crashed.finish(Some(ExitStatus::from_raw(11)));
+        assert_eq!((crashed.exit_code, crashed.success), (None, Some(false)));
+    }
+}
@@ -0,0 +1,224 @@
+//! Game versions parsed from release tags and `VERSION.txt`.
+//!
+//! Tags don't sort as strings: `0.H-1` is newer than `0.H-RELEASE`, and experimental tags have
+//! had several prefixes. [`CddaVersion`] orders them by what they mean.
+use crate::prelude::*;
+use chrono::NaiveDateTime;
+use regex::Regex;
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+const BUILD_TIME_FORMAT: &str = "%Y-%m-%d-%H%M";
+
+/// A CDDA version. Versions of different kinds order as `Build` < `Experimental` < `Stable`,
+/// which only matters when channels are mixed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum CddaVersion {
+    /// Numbered build of the old Jenkins CI. e.g. `cdda-jenkins-b11234`, or `11234` in
+    /// `VERSION.txt`. Bare numbers need 4 digits, so that any number isn't taken for a build.
+    Build(u32),
+    /// Experimental build, named after its build time. e.g. `cdda-experimental-2024-03-01-0613`
+    Experimental(NaiveDateTime),
+    /// Stable release. `point` is 0 for the release itself. e.g. `0.H-RELEASE`, `0.H-1`
+    Stable { letter: char, point: u32 },
+}
+
+struct Patterns {
+    experimental: Regex,
+    stable: Regex,
+    build: Regex,
+}
+
+fn patterns() -> &'static Patterns {
+    static PATTERNS: OnceLock<Patterns> = OnceLock::new();
+    PATTERNS.get_or_init(|| Patterns {
+        // The build time, after a prefix such as `cdda-experimental-`.
+        experimental: Regex::new(r"^(?:[A-Za-z]+-)*(?P<time>\d{4}-\d{2}-\d{2}-\d{4})$").unwrap(),
+        stable: Regex::new(r"^(?:cdda-)?0\.(?P<letter>[A-Z])(?:-(?P<point>\d+|RELEASE))?$")
+            .unwrap(),
+        build: Regex::new(r"^(?:(?:cdda-jenkins-)?b(?P<number>\d+)|(?P<bare>\d{4,}))$").unwrap(),
+    })
+}
+
+impl CddaVersion {
+    pub fn is_experimental(&self) -> bool {
+        !matches!(self, CddaVersion::Stable { .. })
+    }
+}
+
+impl FromStr for CddaVersion {
+    type Err = anyhow::Error;
+
+    /// Parses a release tag or the build number of `VERSION.txt`.
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let patterns = patterns();
+        if let Some(c) = patterns.experimental.captures(s) {
+            let time = NaiveDateTime::parse_from_str(&c["time"], BUILD_TIME_FORMAT)
+                .with_context(|| format!("Invalid build time: {}", s))?;
+            return Ok(CddaVersion::Experimental(time));
+        }
+        if let Some(c) = patterns.stable.captures(s) {
+            let point = match c.name("point").map(|m| m.as_str()) {
+                None | Some("RELEASE") => 0,
+                Some(point) => point.parse()?,
+            };
+            return Ok(CddaVersion::Stable {
+                letter: c["letter"].chars().next().unwrap(),
+                point,
+            });
+        }
+        if let Some(c) = patterns.build.captures(s) {
+            let number = c.name("number").or(c.name("bare")).unwrap();
+            return Ok(CddaVersion::Build(number.as_str().parse()?));
+        }
+        Err(anyhow!("Not a CDDA version: {}", s))
+    }
+}
+
+impl fmt::Display for CddaVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CddaVersion::Build(number) => write!(f, "b{}", number),
+            CddaVersion::Experimental(time) => write!(f, "{}", time.format(BUILD_TIME_FORMAT)),
+            CddaVersion::Stable { letter, point: 0 } => write!(f, "0.{}", letter),
+            CddaVersion::Stable { letter, point } => write!(f, "0.{}-{}", letter, point),
+        }
+    }
+}
+
+impl TryFrom<String> for CddaVersion {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl From<CddaVersion> for String {
+    fn from(version: CddaVersion) -> Self {
+        version.to_string()
+    }
+}
+
+/// Sort key of a release tag. Tags that are not CDDA versions (e.g. of other release sources)
+/// sort as strings, before the versions.
+pub fn tag_key(tag: &str) -> (Option<CddaVersion>, &str) {
+    (tag.parse().ok(), tag)
+}
+
+/// Compares two release tags by version.
+pub fn cmp_tags(a: &str, b: &str) -> Ordering {
+    tag_key(a).cmp(&tag_key(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn experimental(time: &str) -> CddaVersion {
+        CddaVersion::Experimental(NaiveDateTime::parse_from_str(time, BUILD_TIME_FORMAT).unwrap())
+    }
+
+    #[test]
+    fn parses_tags_and_version_files() {
+        let cases = [
+            (
+                "cdda-experimental-2024-03-01-0613",
+                experimental("2024-03-01-0613"),
+            ),
+            ("2024-03-01-0613", experimental("2024-03-01-0613")),
+            (" 2024-03-01-0613\n", experimental("2024-03-01-0613")),
+            (
+                "0.H-RELEASE",
+                CddaVersion::Stable {
+                    letter: 'H',
+                    point: 0,
+                },
+            ),
+            (
+                "cdda-0.G",
+                CddaVersion::Stable {
+                    letter: 'G',
+                    point: 0,
+                },
+            ),
+            (
+                "0.H-1",
+                CddaVersion::Stable {
+                    letter: 'H',
+                    point: 1,
+                },
+            ),
+            ("cdda-jenkins-b11234", CddaVersion::Build(11234)),
+            ("b123", CddaVersion::Build(123)),
+            ("11234", CddaVersion::Build(11234)),
+        ];
+        for (s, expected) in cases {
+            assert_eq!(s.parse::<CddaVersion>().unwrap(), expected, "{}", s);
+        }
+    }
+
+    #[test]
+    fn rejects_other_strings() {
+        for s in [
+            "",
+            "2",
+            "123",
+            "latest",
+            "0.h",
+            "v2024-03-01-0613",
+            "2024-03-01-0613-hotfix",
+            "cdda-experimental-2024-03-01",
+            "2024-13-01-0613",
+        ] {
+            assert!(s.parse::<CddaVersion>().is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn round_trips_through_display_and_serde() {
+        for s in ["2024-03-01-0613", "0.H", "0.H-1", "b11234"] {
+            let version: CddaVersion = s.parse().unwrap();
+            assert_eq!(version.to_string(), s);
+            let json = serde_json::to_string(&version).unwrap();
+            assert_eq!(json, format!("\"{}\"", s));
+            assert_eq!(serde_json::from_str::<CddaVersion>(&json).unwrap(), version);
+        }
+        assert!(serde_json::from_str::<CddaVersion>("\"123\"").is_err());
+    }
+
+    #[test]
+    fn orders_tags_by_version() {
+        let mut tags = vec![
+            "0.H-1",
+            "cdda-experimental-2024-03-01-0613",
+            "0.G",
+            "cdda-jenkins-b11234",
+            "0.H-RELEASE",
+            "latest",
+            "cdda-experimental-2023-12-31-2359",
+            "0.H-10",
+            "0.H-2",
+        ];
+        tags.sort_by(|a, b| cmp_tags(a, b));
+        assert_eq!(
+            tags,
+            [
+                "latest",
+                "cdda-jenkins-b11234",
+                "cdda-experimental-2023-12-31-2359",
+                "cdda-experimental-2024-03-01-0613",
+                "0.G",
+                "0.H-RELEASE",
+                "0.H-1",
+                "0.H-2",
+                "0.H-10",
+            ]
+        );
+        assert_eq!(cmp_tags("0.H", "0.H-RELEASE"), Ordering::Less);
+    }
+}
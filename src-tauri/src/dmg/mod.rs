@@ -24,12 +24,16 @@ fn mount(source_dmg: PathBuf) -> Result<Handle> {
 
 #[derive(Clone, Debug, serde::Serialize)]
 pub struct Progress {
-    progress: u64,
-    total: u64,
+    pub progress: u64,
+    pub total: u64,
     // percent: f64,
 }
 
-fn copy(app_handle: AppHandle, cdda_path: PathBuf, target_dir: PathBuf) -> Result<()> {
+fn copy(
+    mut on_progress: impl FnMut(Progress),
+    cdda_path: PathBuf,
+    target_dir: PathBuf,
+) -> Result<()> {
     use fs_extra::dir::CopyOptions;
     let options = CopyOptions::new()
         // .buffer_size(1024 * 1024 * 10) // 10 MB
@@ -45,15 +49,10 @@ fn copy(app_handle: AppHandle, cdda_path: PathBuf, target_dir: PathBuf) -> Resul
                 "Copying {} of {} bytes",
                 process_info.copied_bytes, process_info.total_bytes
             );
-            app_handle
-                .emit(
-                    "EXTRACT_PROGRESS",
-                    Progress {
-                        progress: process_info.copied_bytes,
-                        total: process_info.total_bytes,
-                    },
-                )
-                .unwrap();
+            on_progress(Progress {
+                progress: process_info.copied_bytes,
+                total: process_info.total_bytes,
+            });
             last_emit = std::time::Instant::now();
         }
         fs_extra::dir::TransitProcessResult::ContinueOrAbort
@@ -62,10 +61,15 @@ fn copy(app_handle: AppHandle, cdda_path: PathBuf, target_dir: PathBuf) -> Resul
     Ok(())
 }
 
-fn mount_and_copy(handle: AppHandle, cdda_path: PathBuf, target_dir: PathBuf) -> Result<()> {
+/// Mounts the DMG and copies `Cataclysm.app` into `target_dir`, reporting the copy progress about once a second.
+pub fn mount_and_copy(
+    on_progress: impl FnMut(Progress),
+    cdda_path: PathBuf,
+    target_dir: PathBuf,
+) -> Result<()> {
     let mount_info = mount(cdda_path.clone())?;
     copy(
-        handle,
+        on_progress,
         mount_info.mount_point.join("Cataclysm.app"),
         target_dir,
     )?;
@@ -84,7 +88,10 @@ pub mod commands {
     ) -> Result<(), String> {
         let source_dmg = PathBuf::from(source_dmg);
         let target_dir = PathBuf::from(target_dir);
-        mount_and_copy(handle, source_dmg, target_dir).map_err(|e| e.to_string())?;
+        let on_progress = |progress: Progress| {
+            handle.emit("EXTRACT_PROGRESS", progress).unwrap();
+        };
+        mount_and_copy(on_progress, source_dmg, target_dir).map_err(|e| e.to_string())?;
         Ok(())
     }
 }
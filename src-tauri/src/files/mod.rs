@@ -125,36 +125,46 @@ pub mod commands {
                 .spawn()
                 .unwrap();
         }
-        // #[cfg(target_os = "linux")]
-        // {
-        //     if path.contains(",") {
-        //         // see https://gitlab.freedesktop.org/dbus/dbus/-/issues/76
-        //         let new_path = match metadata(&path).unwrap().is_dir() {
-        //             true => path,
-        //             false => {
-        //                 let mut path2 = PathBuf::from(path);
-        //                 path2.pop();
-        //                 path2.into_os_string().into_string().unwrap()
-        //             }
-        //         };
-        //         Command::new("xdg-open").arg(&new_path).spawn().unwrap();
-        //     } else {
-        //         if let Ok(Fork::Child) = daemon(false, false) {
-        //             Command::new("dbus-send")
-        //                 .args([
-        //                     "--session",
-        //                     "--dest=org.freedesktop.FileManager1",
-        //                     "--type=method_call",
-        //                     "/org/freedesktop/FileManager1",
-        //                     "org.freedesktop.FileManager1.ShowItems",
-        //                     format!("array:string:\"file://{path}\"").as_str(),
-        //                     "string:\"\"",
-        //                 ])
-        //                 .spawn()
-        //                 .unwrap();
-        //         }
-        //     }
-        // }
+        #[cfg(target_os = "linux")]
+        {
+            use std::fs::metadata;
+            let xdg_open = |path: &str| {
+                if let Err(e) = Command::new("xdg-open").arg(path).spawn() {
+                    error!("Failed to open {} with xdg-open: {}", path, e);
+                }
+            };
+            if target_dir.contains(',') {
+                // see https://gitlab.freedesktop.org/dbus/dbus/-/issues/76
+                let new_path = match metadata(&target_dir).map(|m| m.is_dir()) {
+                    Ok(true) => target_dir,
+                    _ => {
+                        let mut path = PathBuf::from(target_dir);
+                        path.pop();
+                        path.into_os_string().into_string().unwrap()
+                    }
+                };
+                xdg_open(&new_path);
+            } else {
+                // FileManager1 (Nautilus, Dolphin, ...) で選択状態にして開く。
+                // 対応するファイルマネージャがなければxdg-openにフォールバック
+                let shown = Command::new("dbus-send")
+                    .args([
+                        "--session",
+                        "--print-reply",
+                        "--dest=org.freedesktop.FileManager1",
+                        "--type=method_call",
+                        "/org/freedesktop/FileManager1",
+                        "org.freedesktop.FileManager1.ShowItems",
+                        format!("array:string:file://{target_dir}").as_str(),
+                        "string:",
+                    ])
+                    .output()
+                    .is_ok_and(|output| output.status.success());
+                if !shown {
+                    xdg_open(&target_dir);
+                }
+            }
+        }
     }
 
     #[tauri::command]
@@ -1,6 +1,9 @@
 use crate::prelude::*;
 use git2::{Branch, Direction, FetchOptions, Repository, Signature};
-use std::collections::HashSet;
+use sparse::SparseCheckout;
+use std::collections::{BTreeMap, HashSet};
+
+pub mod sparse;
 
 pub fn open(target_dir: String) -> Result<Repository, String> {
     // debug!("Opening repository at {}", target_dir);
@@ -14,31 +17,48 @@ pub fn init(target_dir: String) -> Result<Repository, String> {
         Err(e) => Err(format!("Failed to initialize repository: {}", e)),
     }
 }
-pub fn get_signature() -> Signature<'static> {
-    Signature::now("Catalyzer", "Nothing").unwrap()
+pub fn get_signature() -> Result<Signature<'static>, String> {
+    Signature::now("Catalyzer", "Nothing").map_err(|e| format!("Failed to create signature: {}", e))
 }
 
+/// Commits every change of the working tree. Does nothing if there is nothing to commit.
 pub fn commit(repo: &Repository, message: &str) -> Result<(), String> {
     debug!("Committing changes to repository");
-    let sig = get_signature();
+    let sig = get_signature()?;
     let tree_id = {
-        let mut index = repo.index().unwrap();
+        let mut index = repo
+            .index()
+            .map_err(|e| format!("Failed to read index: {}", e))?;
         index
             .add_all(["*"].iter(), git2::IndexAddOption::DEFAULT, None)
-            .unwrap();
-        index.write_tree().unwrap()
+            .and_then(|_| index.write())
+            .map_err(|e| format!("Failed to stage changes: {}", e))?;
+        index
+            .write_tree()
+            .map_err(|e| format!("Failed to write tree: {}", e))?
     };
-    let tree = repo.find_tree(tree_id).unwrap();
+    let tree = repo
+        .find_tree(tree_id)
+        .map_err(|e| format!("Failed to find tree: {}", e))?;
     let parents = match repo.head() {
         Ok(head) => {
-            let head = head.peel_to_commit().unwrap();
+            let head = head
+                .peel_to_commit()
+                .map_err(|e| format!("Failed to read HEAD: {}", e))?;
             vec![head]
         }
         Err(_) => vec![],
     };
+    if parents
+        .first()
+        .is_some_and(|head| head.tree_id() == tree_id)
+    {
+        debug!("Nothing to commit");
+        return Ok(());
+    }
     let p = &parents.iter().collect::<Vec<_>>();
     repo.commit(Some("HEAD"), &sig, &sig, message, &tree, p.as_slice())
-        .unwrap();
+        .map_err(|e| format!("Failed to commit: {}", e))?;
     Ok(())
 }
 
@@ -61,15 +81,50 @@ pub fn fetch(repo: &Repository, depth: Option<i32>) -> Result<(), String> {
         .map_err(|e| format!("Failed to fetch from remote 'origin': {}", e))
 }
 
-pub fn pull_rebase(repo: &Repository) -> Result<(), String> {
+/// Fetches tags from 'origin', `depth` commits deep. Fetching again with a larger depth
+/// deepens a shallow clone.
+pub fn fetch_tags(repo: &Repository, tags: &[&str], depth: Option<i32>) -> Result<()> {
+    debug!(
+        "Fetching tags {:?} from 'origin'. depth = {:?}",
+        tags, depth
+    );
+    let mut remote = repo.find_remote("origin")?;
+    let refspecs = tags
+        .iter()
+        .map(|tag| format!("+refs/tags/{0}:refs/tags/{0}", tag))
+        .collect::<Vec<_>>();
+    let mut fo = FetchOptions::new();
+    if let Some(depth) = depth {
+        fo.depth(depth);
+    }
+    remote
+        .fetch(&refspecs, Some(&mut fo), None)
+        .with_context(|| format!("Failed to fetch tags {:?}", tags))
+}
+
+/// The commits a shallow clone is cut at. Their parents are missing.
+pub fn shallow_commits(repo: &Repository) -> HashSet<git2::Oid> {
+    std::fs::read_to_string(repo.path().join("shallow"))
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| git2::Oid::from_str(line.trim()).ok())
+        .collect()
+}
+
+/// With `sparse`, the working tree is kept to the sparse paths.
+pub fn pull_rebase(repo: &Repository, sparse: Option<&SparseCheckout>) -> Result<(), String> {
     // fetch the latest changes
     fetch(repo, Some(1))?;
 
     // rebase the current branch to the latest changes
+    let mut options = git2::RebaseOptions::new();
+    if let Some(sparse) = sparse {
+        options.checkout_options(sparse.checkout_builder());
+    }
     let mut rebase = repo
-        .rebase(None, None, None, None)
+        .rebase(None, None, None, Some(&mut options))
         .map_err(|e| format!("Failed to start rebase: {}", e))?;
-    let app_signature = get_signature();
+    let app_signature = get_signature()?;
     while let Some(op) = rebase.next() {
         match op {
             Ok(_) => {
@@ -88,6 +143,11 @@ pub fn pull_rebase(repo: &Repository) -> Result<(), String> {
     rebase
         .finish(None)
         .map_err(|e| format!("Failed to finish rebase: {}", e))?;
+    if let Some(sparse) = sparse {
+        sparse
+            .apply(repo)
+            .map_err(|e| format!("Failed to update the sparse checkout: {:#}", e))?;
+    }
     Ok(())
 }
 
@@ -203,10 +263,33 @@ pub fn git_clone(url: &str, target_dir: &Path, depth1: Option<bool>) -> Result<R
     }
 }
 
+/// Clones with depth 1, checking out only the sparse paths.
+pub fn git_clone_sparse(
+    url: &str,
+    target_dir: &Path,
+    sparse: &SparseCheckout,
+) -> Result<Repository, String> {
+    debug!(
+        "Clone repo from {} to {:?}. sparse paths = {:?}",
+        url, &target_dir, sparse.paths
+    );
+    let mut fetch_opts = FetchOptions::new();
+    fetch_opts.depth(1);
+    let mut builder = git2::build::RepoBuilder::new();
+    builder
+        .fetch_options(fetch_opts)
+        .with_checkout(sparse.checkout_builder());
+    let repo = builder
+        .clone(url, target_dir)
+        .map_err(|e| format!("Failed to clone repository: {}", e))?;
+    sparse
+        .apply(&repo)
+        .map_err(|e| format!("Failed to set up the sparse checkout: {:#}", e))?;
+    Ok(repo)
+}
+
 fn get_tmp_dir_path() -> PathBuf {
-    use crate::paths::get_app_data_dir;
-    let app_data_dir = get_app_data_dir();
-    let tmp_path = app_data_dir.join(".cdda").join("tmp");
+    let tmp_path = crate::paths::tmp_dir();
     if !tmp_path.exists() {
         debug!("init tmp dir: {:?}", &tmp_path);
         std::fs::create_dir_all(&tmp_path).unwrap();
@@ -236,6 +319,34 @@ pub fn ls_remote_tags(url: String) -> Result<Vec<String>> {
     Ok(tags.into_iter().collect())
 }
 
+/// Lists the tags of a remote repository with the commit each points at.
+/// Annotated tags are peeled to their commit.
+pub fn ls_remote_tag_commits(url: String) -> Result<BTreeMap<String, String>> {
+    let tmp = get_tmp_dir_path();
+    let repo = git2::Repository::init(tmp)?;
+    let mut remote = repo.remote_anonymous(&url)?;
+    let connection = remote
+        .connect_auth(Direction::Fetch, None, None)
+        .context("Failed to connect to remote repository. Please check your network connection.")?;
+    let mut tags = BTreeMap::new();
+    for head in connection.list()? {
+        let Some(name) = head.name().strip_prefix("refs/tags/") else {
+            continue;
+        };
+        match name.strip_suffix("^{}") {
+            // The peeled entry of an annotated tag names its commit.
+            Some(name) => {
+                tags.insert(name.to_string(), head.oid().to_string());
+            }
+            None => {
+                tags.entry(name.to_string())
+                    .or_insert_with(|| head.oid().to_string());
+            }
+        }
+    }
+    Ok(tags)
+}
+
 pub mod commands {
     use super::*;
     use crate::profile::AppState;
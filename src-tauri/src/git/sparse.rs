@@ -0,0 +1,221 @@
+//! Sparse working trees.
+//!
+//! libgit2 has no sparse checkout, so checkouts are limited to the sparse paths, and the other
+//! index entries are flagged skip-worktree. `core.sparseCheckout` and `info/sparse-checkout` are
+//! written as well, so that the git command line agrees.
+//!
+//! Only clones created sparse, or made sparse on request, are kept sparse. Existing full clones
+//! are left alone.
+use crate::prelude::*;
+use git2::build::CheckoutBuilder;
+use git2::{IndexEntryExtendedFlag, IndexEntryFlag, Repository};
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_paths() -> Vec<String> {
+    ["data/", "lang/", "doc/"].map(String::from).to_vec()
+}
+
+/// The directories kept in a working tree.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct SparseCheckout {
+    /// New clones are sparse. An existing clone only changes when the setting is applied to it.
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Relative to the repository root, e.g. `data/`
+    #[serde(default = "default_paths")]
+    pub paths: Vec<String>,
+}
+
+impl Default for SparseCheckout {
+    fn default() -> Self {
+        Self {
+            enabled: default_enabled(),
+            paths: default_paths(),
+        }
+    }
+}
+
+/// Whether the working tree of `repo` is sparse (`core.sparseCheckout`).
+pub fn is_sparse(repo: &Repository) -> bool {
+    repo.config()
+        .and_then(|config| config.get_bool("core.sparseCheckout"))
+        .unwrap_or(false)
+}
+
+/// Checks out the whole tree of a sparse clone, which stays full from then on.
+pub fn widen(repo: &Repository) -> Result<()> {
+    let full = SparseCheckout {
+        enabled: false,
+        paths: Vec::new(),
+    };
+    full.apply(repo)
+}
+
+impl SparseCheckout {
+    fn dirs(&self) -> impl Iterator<Item = &str> {
+        self.paths
+            .iter()
+            .map(|p| p.trim_matches('/'))
+            .filter(|p| !p.is_empty())
+    }
+
+    /// Whether a path of the repository is in the working tree.
+    pub fn contains(&self, path: &str) -> bool {
+        !self.enabled
+            || self.dirs().any(|dir| {
+                path.strip_prefix(dir)
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+            })
+    }
+
+    /// A checkout limited to the sparse paths.
+    pub fn checkout_builder(&self) -> CheckoutBuilder<'static> {
+        let mut builder = CheckoutBuilder::new();
+        if self.enabled {
+            for dir in self.dirs() {
+                builder.path(dir);
+            }
+        }
+        builder
+    }
+
+    fn write_config(&self, repo: &Repository) -> Result<()> {
+        repo.config()?
+            .set_bool("core.sparseCheckout", self.enabled)?;
+        let file = repo.path().join("info").join("sparse-checkout");
+        if self.enabled {
+            std::fs::create_dir_all(file.parent().unwrap())?;
+            let patterns = self
+                .dirs()
+                .map(|dir| format!("/{}/\n", dir))
+                .collect::<String>();
+            std::fs::write(&file, patterns)?;
+        } else if file.exists() {
+            std::fs::remove_file(&file)?;
+        }
+        Ok(())
+    }
+
+    /// Brings the working tree of HEAD in line with the sparse paths: checks out the paths,
+    /// removes the tracked files outside them and rebuilds the index.
+    /// With `enabled` off, the whole tree is checked out again.
+    pub fn apply(&self, repo: &Repository) -> Result<()> {
+        let workdir = repo
+            .workdir()
+            .ok_or_else(|| anyhow!("Bare repository"))?
+            .to_path_buf();
+        self.write_config(repo)?;
+        let tree = repo.head()?.peel_to_tree()?;
+        repo.checkout_tree(tree.as_object(), Some(self.checkout_builder().force()))?;
+
+        let mut index = repo.index()?;
+        index.read_tree(&tree)?;
+        let entries = index.iter().collect::<Vec<_>>();
+        for mut entry in entries {
+            let path = String::from_utf8_lossy(&entry.path).to_string();
+            if self.contains(&path) {
+                continue;
+            }
+            entry.flags |= IndexEntryFlag::EXTENDED.bits();
+            entry.flags_extended |= IndexEntryExtendedFlag::SKIP_WORKTREE.bits();
+            index.add(&entry)?;
+        }
+        index.write()?;
+
+        if self.enabled {
+            // Only whole top-level entries are removed. A sparse path keeps its top directory.
+            for entry in tree.iter() {
+                let Some(name) = entry.name() else {
+                    continue;
+                };
+                let kept = self.dirs().any(|dir| dir.split('/').next() == Some(name));
+                let path = workdir.join(name);
+                if kept || !path.exists() {
+                    continue;
+                }
+                debug!("Removing {} from the sparse checkout", path.display());
+                if path.is_dir() {
+                    crate::files::remove_dir_all(&path, None)?;
+                } else {
+                    std::fs::remove_file(&path)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A repository with game data, sources and a top-level build file, all committed.
+    fn game_repo(dir: &Path) -> Repository {
+        let repo = Repository::init(dir).unwrap();
+        for (path, content) in [
+            ("data/json/items.json", "[]"),
+            ("src/main.cpp", "int main() {}"),
+            ("Makefile", "all:"),
+        ] {
+            let path = dir.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+        {
+            let mut index = repo.index().unwrap();
+            index
+                .add_all(["*"], git2::IndexAddOption::DEFAULT, None)
+                .unwrap();
+            index.write().unwrap();
+            let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+            let signature = git2::Signature::now("test", "test@example.com").unwrap();
+            repo.commit(Some("HEAD"), &signature, &signature, "Initial", &tree, &[])
+                .unwrap();
+        }
+        repo
+    }
+
+    #[test]
+    fn a_full_clone_is_not_sparse() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(!is_sparse(&game_repo(dir.path())));
+    }
+
+    #[test]
+    fn keeps_the_sparse_paths_and_widens_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = game_repo(dir.path());
+        let sparse = SparseCheckout {
+            enabled: true,
+            paths: vec!["data/".to_string()],
+        };
+        sparse.apply(&repo).unwrap();
+        assert!(is_sparse(&repo));
+        assert!(dir.path().join("data/json/items.json").is_file());
+        assert!(!dir.path().join("src").exists());
+        assert!(!dir.path().join("Makefile").exists());
+
+        widen(&repo).unwrap();
+        assert!(!is_sparse(&repo));
+        assert!(dir.path().join("src/main.cpp").is_file());
+        assert!(dir.path().join("Makefile").is_file());
+        assert!(repo.statuses(None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn contains_only_whole_directories() {
+        let sparse = SparseCheckout::default();
+        assert!(sparse.contains("data"));
+        assert!(sparse.contains("data/json/items.json"));
+        assert!(!sparse.contains("database/x"));
+        assert!(!sparse.contains("src/main.cpp"));
+        let full = SparseCheckout {
+            enabled: false,
+            ..SparseCheckout::default()
+        };
+        assert!(full.contains("src/main.cpp"));
+    }
+}
@@ -0,0 +1,386 @@
+//! Building `cataclysm-tiles` from a source checkout (Linux only).
+//!
+//! The game is built in place, so the checkout itself becomes a local `GameInstall`. Rebuilding
+//! replaces the registry entry and keeps the profiles using it.
+use super::{GameInstall, ReleaseChannel};
+use crate::prelude::*;
+use std::io::{BufRead, BufReader, Read};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+
+pub const BUILD_OUTPUT: &str = "BUILD_OUTPUT";
+
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BuildSystem {
+    #[default]
+    Make,
+    Cmake,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize, Clone)]
+pub struct BuildSettings {
+    /// Source checkouts offered for building, besides the CDDA clone.
+    #[serde(default)]
+    pub checkouts: Vec<PathBuf>,
+    #[serde(default)]
+    pub system: BuildSystem,
+    /// Parallel jobs. Defaults to the number of CPUs.
+    #[serde(default)]
+    pub jobs: Option<usize>,
+    /// Extra arguments to make (e.g. `LOCALIZE=0`), or to the cmake configure step.
+    #[serde(default)]
+    pub extra_args: Vec<String>,
+}
+
+impl BuildSettings {
+    /// The checkouts that can be built: the CDDA clone, then `checkouts`.
+    pub fn sources(&self) -> Vec<PathBuf> {
+        std::iter::once(crate::paths::cdda_clone_dir())
+            .chain(self.checkouts.iter().cloned())
+            .collect()
+    }
+
+    /// Finds a build source by directory name. Anything else is taken as a path.
+    /// Defaults to the CDDA clone.
+    pub fn resolve_source(&self, source: Option<&str>) -> PathBuf {
+        let Some(source) = source else {
+            return crate::paths::cdda_clone_dir();
+        };
+        self.sources()
+            .into_iter()
+            .find(|dir| dir.file_name().is_some_and(|name| name == source))
+            .unwrap_or_else(|| PathBuf::from(source))
+    }
+
+    fn jobs(&self) -> usize {
+        self.jobs
+            .or_else(|| std::thread::available_parallelism().ok().map(|n| n.get()))
+            .unwrap_or(1)
+            .max(1)
+    }
+}
+
+/// The cancellation flag of the running build. One build runs at a time.
+#[derive(Debug, Default)]
+pub struct BuildState {
+    running: std::sync::Mutex<Option<Arc<AtomicBool>>>,
+}
+
+impl BuildState {
+    /// The build is finished when the returned guard is dropped, even if the build panicked.
+    fn start(&self) -> Result<(Arc<AtomicBool>, FinishOnDrop<'_>)> {
+        let mut running = self.running.lock().unwrap();
+        ensure!(running.is_none(), "A build is already running");
+        let cancel = Arc::new(AtomicBool::new(false));
+        *running = Some(cancel.clone());
+        Ok((cancel, FinishOnDrop(self)))
+    }
+
+    fn finish(&self) {
+        self.running.lock().unwrap().take();
+    }
+
+    /// Requests cancellation. Returns false if no build is running.
+    pub fn cancel(&self) -> bool {
+        match self.running.lock().unwrap().as_ref() {
+            Some(cancel) => {
+                cancel.store(true, Ordering::SeqCst);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+struct FinishOnDrop<'a>(&'a BuildState);
+
+impl Drop for FinishOnDrop<'_> {
+    fn drop(&mut self) {
+        self.0.finish();
+    }
+}
+
+/// Sends each line of a stream of a build step, flagged with whether it is stderr.
+fn forward_lines(
+    stream: impl Read + Send + 'static,
+    stderr: bool,
+    tx: Sender<(bool, String)>,
+) -> std::thread::JoinHandle<()> {
+    std::thread::spawn(move || {
+        BufReader::new(stream)
+            .lines()
+            .map_while(|line| line.ok())
+            .for_each(|line| tx.send((stderr, line)).unwrap_or_default())
+    })
+}
+
+/// The processes started by `pid`, directly or not, read from `/proc`.
+fn descendants(pid: u32) -> Vec<u32> {
+    let parents = std::fs::read_dir("/proc")
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse::<u32>().ok())
+        .filter_map(|other| {
+            let stat = std::fs::read_to_string(format!("/proc/{}/stat", other)).ok()?;
+            // "<pid> (<comm>) <state> <ppid> ...". The command name may contain spaces.
+            let ppid = stat
+                .rsplit_once(')')?
+                .1
+                .split_whitespace()
+                .nth(1)?
+                .parse()
+                .ok()?;
+            Some((other, ppid))
+        })
+        .collect::<Vec<(u32, u32)>>();
+    let mut found = vec![pid];
+    let mut i = 0;
+    while i < found.len() {
+        let parent = found[i];
+        found.extend(
+            parents
+                .iter()
+                .filter(|(_, p)| *p == parent)
+                .map(|(c, _)| *c),
+        );
+        i += 1;
+    }
+    found.split_off(1)
+}
+
+/// Stops a build step along with the compilers it started.
+fn kill_tree(child: &mut Child) {
+    let pids = descendants(child.id());
+    if !pids.is_empty() {
+        Command::new("kill")
+            .arg("-TERM")
+            .args(pids.iter().map(|pid| pid.to_string()))
+            .status()
+            .map_err(|e| warn!("Failed to stop the build: {}", e))
+            .ok();
+    }
+    child
+        .kill()
+        .unwrap_or_else(|e| warn!("Failed to stop the build: {}", e));
+}
+
+/// Runs a build step, logging its output and passing it to `on_output`.
+/// Polls `cancel` until the step exits.
+fn run_step(
+    mut command: Command,
+    cancel: &AtomicBool,
+    on_output: &mut impl FnMut(&str),
+) -> Result<()> {
+    info!("[build] $ {:?}", command);
+    let program = command.get_program().to_string_lossy().to_string();
+    let mut child = command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to run {}. Is it installed?", program))?;
+    let (tx, rx) = std::sync::mpsc::channel();
+    let readers = [
+        forward_lines(child.stdout.take().unwrap(), false, tx.clone()),
+        forward_lines(child.stderr.take().unwrap(), true, tx),
+    ];
+    let mut output = || {
+        for (stderr, line) in rx.try_iter() {
+            match stderr {
+                true => warn!("[build] {}", line),
+                false => info!("[build] {}", line),
+            }
+            on_output(&line);
+        }
+    };
+    let status = loop {
+        output();
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if cancel.load(Ordering::SeqCst) {
+            kill_tree(&mut child);
+            child.wait()?;
+            return Err(anyhow!("Build cancelled"));
+        }
+        std::thread::sleep(std::time::Duration::from_millis(200));
+    };
+    for reader in readers {
+        reader.join().ok();
+    }
+    output();
+    ensure!(status.success(), "{} failed: {}", program, status);
+    Ok(())
+}
+
+fn build_with_make(
+    source_dir: &Path,
+    settings: &BuildSettings,
+    cancel: &AtomicBool,
+    on_output: &mut impl FnMut(&str),
+) -> Result<()> {
+    let mut make = Command::new("make");
+    make.current_dir(source_dir)
+        .arg(format!("-j{}", settings.jobs()))
+        .args(["TILES=1", "SOUND=1", "RELEASE=1"])
+        .args(&settings.extra_args);
+    run_step(make, cancel, on_output)
+}
+
+/// Builds in `<source>/build` and copies the binary next to `data/`, where the game expects it.
+fn build_with_cmake(
+    source_dir: &Path,
+    settings: &BuildSettings,
+    cancel: &AtomicBool,
+    on_output: &mut impl FnMut(&str),
+) -> Result<()> {
+    let build_dir = source_dir.join("build");
+    let mut configure = Command::new("cmake");
+    configure
+        .current_dir(source_dir)
+        .arg("-S")
+        .arg(source_dir)
+        .arg("-B")
+        .arg(&build_dir)
+        .args(["-DCMAKE_BUILD_TYPE=Release", "-DTILES=ON", "-DSOUND=ON"])
+        .args(&settings.extra_args);
+    run_step(configure, cancel, on_output)?;
+
+    let mut build = Command::new("cmake");
+    build
+        .current_dir(source_dir)
+        .arg("--build")
+        .arg(&build_dir)
+        .args(["--target", "cataclysm-tiles", "--parallel"])
+        .arg(settings.jobs().to_string());
+    run_step(build, cancel, on_output)?;
+
+    let binary = super::find_game_path(&build_dir)
+        .ok_or_else(|| anyhow!("cataclysm-tiles not found in {}", build_dir.display()))?;
+    let target = source_dir.join("cataclysm-tiles");
+    if binary != target {
+        std::fs::copy(&binary, &target)
+            .with_context(|| format!("Failed to copy {}", binary.display()))?;
+    }
+    Ok(())
+}
+
+/// Builds the game in `source_dir` and returns it as a local install. The install is not
+/// registered yet.
+///
+/// A sparse clone is checked out in full first, as building needs the sources.
+/// The compiler output is logged line by line, and passed to `on_output`.
+pub fn build_game(
+    source_dir: &Path,
+    settings: &BuildSettings,
+    cancel: &AtomicBool,
+    mut on_output: impl FnMut(&str),
+) -> Result<GameInstall> {
+    ensure!(
+        cfg!(target_os = "linux"),
+        "Building the game is only supported on Linux"
+    );
+    if let Ok(repo) = git2::Repository::open(source_dir) {
+        if crate::git::sparse::is_sparse(&repo) {
+            info!("Checking out the whole tree of {}", source_dir.display());
+            on_output("Checking out the sources");
+            crate::git::sparse::widen(&repo).context("Failed to check out the sources")?;
+        }
+    }
+    ensure!(
+        source_dir.join("src").is_dir(),
+        "No game sources in {}",
+        source_dir.display()
+    );
+    info!(
+        "Building {} with {:?}, {} jobs",
+        source_dir.display(),
+        settings.system,
+        settings.jobs()
+    );
+    match settings.system {
+        BuildSystem::Make => build_with_make(source_dir, settings, cancel, &mut on_output)?,
+        BuildSystem::Cmake => build_with_cmake(source_dir, settings, cancel, &mut on_output)?,
+    }
+    let mut install = GameInstall::detect(source_dir, None, ReleaseChannel::Local)?;
+    // Built from a source checkout, not from the release of a source.
+    install.id = format!("build_{}", install.installed_at.format("%Y%m%d%H%M%S"));
+    Ok(install)
+}
+
+pub mod commands {
+    use super::*;
+    use crate::cdda::session::SessionState;
+    use tauri::{AppHandle, Manager};
+
+    /// The checkouts that can be built, the CDDA clone first.
+    #[tauri::command]
+    pub fn list_build_sources(state: tauri::State<'_, AppState>) -> Vec<PathBuf> {
+        state.get_settings().unwrap().build.sources()
+    }
+
+    /// Builds the game from `source_dir` and registers it. `source_dir` is a path, or the
+    /// directory name of a build source, and defaults to the CDDA clone.
+    /// If `profile_id` is given, the profile is switched to the build, which is refused while
+    /// its game is running. The compiler output is emitted line by line as `BUILD_OUTPUT`.
+    #[tauri::command]
+    pub async fn build_game(
+        app: AppHandle,
+        source_dir: Option<String>,
+        profile_id: Option<String>,
+    ) -> Result<GameInstall, String> {
+        tauri::async_runtime::spawn_blocking(move || {
+            let sessions = app.state::<SessionState>();
+            if let Some(profile_id) = &profile_id {
+                sessions
+                    .ensure_not_running(profile_id)
+                    .map_err(|e| e.to_string())?;
+            }
+            let build_settings = app.state::<AppState>().get_settings().unwrap().build;
+            let source_dir = build_settings.resolve_source(source_dir.as_deref());
+            let builds = app.state::<BuildState>();
+            let install = {
+                let (cancel, _finish) = builds.start().map_err(|e| e.to_string())?;
+                super::build_game(&source_dir, &build_settings, &cancel, |line| {
+                    app.emit(BUILD_OUTPUT, line)
+                        .unwrap_or_else(|e| warn!("Failed to emit build output: {}", e));
+                })
+                .map_err(|e| format!("{:#}", e))?
+            };
+
+            app.state::<AppState>().update_settings(|settings| {
+                settings.register_install(install.clone());
+                if let Some(profile_id) = &profile_id {
+                    sessions.ensure_not_running(profile_id)?;
+                    settings.set_profile_install(profile_id, &install.id)?;
+                }
+                Ok(())
+            })?;
+            info!("Built {}", install.game_path.display());
+            Ok(install)
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+
+    #[tauri::command]
+    pub fn cancel_build_game(builds: tauri::State<'_, BuildState>) -> Result<(), String> {
+        match builds.cancel() {
+            true => Ok(()),
+            false => Err("No build is running".to_string()),
+        }
+    }
+
+    #[tauri::command]
+    pub fn set_build_settings(
+        state: tauri::State<'_, AppState>,
+        build: BuildSettings,
+    ) -> Result<(), String> {
+        state.update_settings(|settings| {
+            settings.set_build_settings(build);
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,642 @@
+//! Resumable downloads into a content-addressed cache.
+//!
+//! An interrupted download is kept as a `.part` file and resumed with an HTTP Range request.
+//! The `ETag` or `Last-Modified` of the download is kept next to it and sent as `If-Range`, so
+//! that a file changed on the server is downloaded again rather than spliced.
+//! Verified files are stored as `sha256/<hex>/<file name>` in the download cache and indexed
+//! by URL, so that reinstalling a build needs no network. `prune_cache` removes the unused ones.
+use super::pipeline::InstallStage;
+use crate::prelude::*;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, SystemTime};
+
+const INDEX_FILENAME: &str = "index.json";
+const BUF_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+struct IndexEntry {
+    /// `sha256:<hex>`
+    digest: String,
+    size: u64,
+}
+
+/// Digests of the downloaded URLs, for assets that don't publish one.
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct CacheIndex {
+    entries: HashMap<String, IndexEntry>,
+}
+
+fn cache_index() -> &'static std::sync::Mutex<CacheIndex> {
+    static INDEX: OnceLock<std::sync::Mutex<CacheIndex>> = OnceLock::new();
+    INDEX.get_or_init(|| {
+        let path = crate::paths::download_cache_dir().join(INDEX_FILENAME);
+        let index = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|s| {
+                serde_json::from_str(&s)
+                    .map_err(|e| warn!("Ignoring broken download index: {}", e))
+                    .ok()
+            })
+            .unwrap_or_default();
+        std::sync::Mutex::new(index)
+    })
+}
+
+/// Replaces the index file atomically, so that a crash can't leave it truncated.
+fn write_index(index: &CacheIndex) -> Result<()> {
+    let dir = crate::paths::download_cache_dir();
+    std::fs::create_dir_all(&dir)?;
+    let mut file = tempfile::NamedTempFile::new_in(&dir)?;
+    serde_json::to_writer(&mut file, index)?;
+    file.persist(dir.join(INDEX_FILENAME))?;
+    Ok(())
+}
+
+fn record(url: &str, entry: IndexEntry) {
+    let mut index = cache_index().lock().unwrap();
+    index.entries.insert(url.to_string(), entry);
+    write_index(&index).unwrap_or_else(|e| warn!("Failed to write download index: {:#}", e));
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn ensure_not_cancelled(cancel: &AtomicBool) -> Result<()> {
+    ensure!(!cancel.load(Ordering::SeqCst), "Install cancelled");
+    Ok(())
+}
+
+/// The cache directory of a digest. Holds a single file, named after the download.
+fn blob_dir(digest: &str) -> Option<PathBuf> {
+    let hex = digest.strip_prefix("sha256:")?.to_lowercase();
+    (hex.len() == 64 && hex.chars().all(|c| c.is_ascii_hexdigit()))
+        .then(|| crate::paths::download_cache_dir().join("sha256").join(hex))
+}
+
+fn blob_in(dir: &Path) -> Option<PathBuf> {
+    std::fs::read_dir(dir)
+        .ok()?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .find(|p| p.is_file())
+}
+
+/// The file holding the validator of a partial download.
+fn validator_path(part: &Path) -> PathBuf {
+    let mut path = part.as_os_str().to_owned();
+    path.push(".validator");
+    PathBuf::from(path)
+}
+
+/// A strong `ETag`, or else `Last-Modified`. Weak ETags can't be sent as `If-Range`.
+fn validator(response: &reqwest::blocking::Response) -> Option<String> {
+    let header = |name: reqwest::header::HeaderName| {
+        let value = response.headers().get(name)?.to_str().ok()?;
+        Some(value.to_string())
+    };
+    header(reqwest::header::ETAG)
+        .filter(|etag| !etag.starts_with("W/"))
+        .or_else(|| header(reqwest::header::LAST_MODIFIED))
+}
+
+fn remove_if_exists(path: &Path) {
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            warn!("Failed to remove {}: {}", path.display(), e)
+        }
+        _ => {}
+    }
+}
+
+/// Removes a partial download and its validator.
+fn remove_part(part: &Path) {
+    remove_if_exists(part);
+    remove_if_exists(&validator_path(part));
+}
+
+/// Marks a cached file as used, so that `prune_cache` keeps it.
+fn touch(path: &Path) {
+    File::options()
+        .write(true)
+        .open(path)
+        .and_then(|file| file.set_modified(SystemTime::now()))
+        .unwrap_or_else(|e| warn!("Failed to touch {}: {}", path.display(), e));
+}
+
+/// Computes the SHA-256 digest (`sha256:<hex>`) of a file.
+pub fn hash_file(
+    path: &Path,
+    cancel: &AtomicBool,
+    mut on_progress: impl FnMut(u64, u64),
+) -> Result<String> {
+    let mut file = File::open(path)?;
+    let total = file.metadata()?.len();
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0; BUF_SIZE];
+    let mut read = 0;
+    let mut last_emit = std::time::Instant::now();
+    loop {
+        ensure_not_cancelled(cancel)?;
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+        read += n as u64;
+        if last_emit.elapsed() > std::time::Duration::from_millis(200) {
+            on_progress(read, total);
+            last_emit = std::time::Instant::now();
+        }
+    }
+    on_progress(read, total);
+    Ok(format!("sha256:{}", hex(&hasher.finalize())))
+}
+
+/// Checks a file against the size and digest advertised by the release.
+pub fn verify(
+    expected_size: Option<u64>,
+    expected_digest: Option<&str>,
+    size: u64,
+    digest: &str,
+) -> Result<()> {
+    if let Some(expected) = expected_size {
+        ensure!(
+            size == expected,
+            "Size mismatch: expected {} bytes, got {} bytes",
+            expected,
+            size
+        );
+    }
+    match expected_digest {
+        Some(expected) if expected.starts_with("sha256:") => ensure!(
+            expected.eq_ignore_ascii_case(digest),
+            "Digest mismatch: expected {}, got {}",
+            expected,
+            digest
+        ),
+        Some(expected) => warn!("Unsupported digest, skipped verification: {}", expected),
+        None => debug!("No digest published. Verified the size only."),
+    }
+    Ok(())
+}
+
+/// The first byte of a `Content-Range: bytes <start>-<end>/<total>` header.
+fn content_range_start(response: &reqwest::blocking::Response) -> Option<u64> {
+    response
+        .headers()
+        .get(reqwest::header::CONTENT_RANGE)?
+        .to_str()
+        .ok()?
+        .strip_prefix("bytes ")?
+        .split('-')
+        .next()?
+        .parse()
+        .ok()
+}
+
+/// Downloads `url` into `part`, resuming from its current length with a Range request.
+/// The download restarts when the file changed on the server, when the server ignores the range,
+/// and when no validator was kept for `part`. Returns the size of the file.
+pub fn download(
+    url: &str,
+    part: &Path,
+    expected_size: Option<u64>,
+    cancel: &AtomicBool,
+    mut on_progress: impl FnMut(u64, u64),
+) -> Result<u64> {
+    if let Some(parent) = part.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut offset = part.metadata().map(|m| m.len()).unwrap_or(0);
+    match expected_size {
+        Some(size) if offset == size => {
+            debug!("Already downloaded: {}", part.display());
+            return Ok(offset);
+        }
+        Some(size) if offset > size => {
+            warn!("Discarding oversized {}", part.display());
+            offset = 0;
+        }
+        _ => {}
+    }
+    let validator_file = validator_path(part);
+    let if_range = std::fs::read_to_string(&validator_file).ok();
+    if offset > 0 && if_range.is_none() {
+        info!("No validator to resume {}, restarting", part.display());
+        offset = 0;
+    }
+
+    let client = reqwest::blocking::Client::builder()
+        .user_agent("catalyzer")
+        .build()?;
+    let mut request = client.get(url);
+    if let Some(if_range) = if_range.filter(|_| offset > 0) {
+        request = request
+            .header(reqwest::header::RANGE, format!("bytes={}-", offset))
+            .header(reqwest::header::IF_RANGE, if_range);
+    }
+    let response = request.send()?;
+    if offset > 0 && response.status() == reqwest::StatusCode::RANGE_NOT_SATISFIABLE {
+        // Nothing left to download. Verification catches a file changed on the server.
+        return Ok(offset);
+    }
+    let mut response = response.error_for_status()?;
+    let resumed = offset > 0
+        && response.status() == reqwest::StatusCode::PARTIAL_CONTENT
+        && content_range_start(&response) == Some(offset);
+    let (mut file, start) = if resumed {
+        info!("Resuming {} from {} bytes", url, offset);
+        (OpenOptions::new().append(true).open(part)?, offset)
+    } else {
+        info!("Downloading {} to {}", url, part.display());
+        let file = File::create(part)?;
+        match validator(&response) {
+            Some(validator) => std::fs::write(&validator_file, validator)?,
+            None => remove_if_exists(&validator_file),
+        }
+        (file, 0)
+    };
+    let total = response
+        .content_length()
+        .map(|len| len + start)
+        .or(expected_size)
+        .unwrap_or(0);
+
+    let mut buf = vec![0; BUF_SIZE];
+    let mut received = start;
+    let mut last_emit = std::time::Instant::now();
+    loop {
+        ensure_not_cancelled(cancel)?;
+        let n = response.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        file.write_all(&buf[..n])?;
+        received += n as u64;
+        if last_emit.elapsed() > std::time::Duration::from_millis(200) {
+            on_progress(received, total);
+            last_emit = std::time::Instant::now();
+        }
+    }
+    file.flush()?;
+    on_progress(received, total);
+    Ok(received)
+}
+
+/// Finds a cached copy of `url`, by the published digest or by the digest recorded on download.
+fn lookup(url: &str, expected_digest: Option<&str>) -> Option<(PathBuf, String)> {
+    let digest = expected_digest
+        .filter(|d| d.starts_with("sha256:"))
+        .map(|d| d.to_lowercase())
+        .or_else(|| {
+            let index = cache_index().lock().unwrap();
+            index.entries.get(url).map(|e| e.digest.clone())
+        })?;
+    let path = blob_in(&blob_dir(&digest)?)?;
+    Some((path, digest))
+}
+
+/// Returns a verified copy of `url`, from the download cache if possible.
+///
+/// An interrupted download is resumed on the next call. A file failing verification is removed.
+pub fn fetch(
+    url: &str,
+    file_name: &str,
+    expected_size: Option<u64>,
+    expected_digest: Option<&str>,
+    cancel: &AtomicBool,
+    mut on_progress: impl FnMut(InstallStage, u64, u64),
+) -> Result<PathBuf> {
+    if let Some((path, digest)) = lookup(url, expected_digest) {
+        info!("Using cached download: {}", path.display());
+        on_progress(InstallStage::Verify, 0, 0);
+        let size = path.metadata()?.len();
+        let actual = hash_file(&path, cancel, |p, t| {
+            on_progress(InstallStage::Verify, p, t)
+        })?;
+        match verify(expected_size, Some(&digest), size, &actual) {
+            Ok(()) => {
+                touch(&path);
+                return Ok(path);
+            }
+            Err(e) => {
+                warn!(
+                    "Discarding corrupt cached download {}: {:#}",
+                    path.display(),
+                    e
+                );
+                if let Some(dir) = path.parent() {
+                    std::fs::remove_dir_all(dir)?;
+                }
+            }
+        }
+    }
+
+    // Keyed by URL, as different sources may publish files with the same name.
+    let url_hash = hex(&Sha256::digest(url.as_bytes()));
+    let part = crate::paths::download_cache_dir()
+        .join("partial")
+        .join(format!("{}-{}.part", &url_hash[..16], file_name));
+    let size = download(url, &part, expected_size, cancel, |p, t| {
+        on_progress(InstallStage::Download, p, t)
+    })?;
+
+    on_progress(InstallStage::Verify, 0, 0);
+    let digest = hash_file(&part, cancel, |p, t| {
+        on_progress(InstallStage::Verify, p, t)
+    })?;
+    if let Err(e) = verify(expected_size, expected_digest, size, &digest) {
+        remove_part(&part);
+        return Err(e);
+    }
+
+    let dir = blob_dir(&digest).ok_or_else(|| anyhow!("Invalid digest: {}", digest))?;
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join(file_name);
+    std::fs::rename(&part, &path)?;
+    remove_part(&part);
+    record(url, IndexEntry { digest, size });
+    Ok(path)
+}
+
+/// Whether `path` was last modified more than `max_age` ago.
+fn is_stale(path: &Path, max_age: Duration) -> bool {
+    path.metadata()
+        .and_then(|m| m.modified())
+        .is_ok_and(|t| SystemTime::now().duration_since(t).unwrap_or_default() > max_age)
+}
+
+fn size_of(path: &Path) -> u64 {
+    match std::fs::read_dir(path) {
+        Ok(entries) => entries
+            .filter_map(|e| e.ok())
+            .map(|e| size_of(&e.path()))
+            .sum(),
+        Err(_) => path.metadata().map(|m| m.len()).unwrap_or(0),
+    }
+}
+
+/// Cached files and partial downloads unused for `max_age`, and validators left without their
+/// partial download.
+fn stale_entries(max_age: Duration) -> Vec<PathBuf> {
+    let dir = crate::paths::download_cache_dir();
+    let list = |dir: PathBuf| -> Vec<PathBuf> {
+        std::fs::read_dir(dir)
+            .map(|entries| entries.filter_map(|e| e.ok()).map(|e| e.path()).collect())
+            .unwrap_or_default()
+    };
+    let mut stale = Vec::new();
+    for blob_dir in list(dir.join("sha256")) {
+        // A directory without its file yet may be in use by a download being stored.
+        let last_used = blob_in(&blob_dir).unwrap_or_else(|| blob_dir.clone());
+        if is_stale(&last_used, max_age) {
+            stale.push(blob_dir);
+        }
+    }
+    for path in list(dir.join("partial")) {
+        let part = match path.to_str().and_then(|p| p.strip_suffix(".validator")) {
+            Some(part) => PathBuf::from(part),
+            None => path.clone(),
+        };
+        if !part.exists() || is_stale(&part, max_age) {
+            stale.push(path);
+        }
+    }
+    stale
+}
+
+/// Removes the downloads unused for `max_age` from the cache, with the interrupted downloads
+/// and the index entries of removed files. With `apply` unset, nothing is removed.
+/// Returns the disk space freed.
+pub fn prune_cache(max_age: Duration, apply: bool) -> Result<u64> {
+    let stale = stale_entries(max_age);
+    if !apply {
+        return Ok(stale.iter().map(|p| size_of(p)).sum());
+    }
+    // Keeps lookups from finding a file being removed.
+    let mut index = cache_index().lock().unwrap();
+    let mut freed = 0;
+    for path in stale {
+        let size = size_of(&path);
+        let result = if path.is_dir() {
+            std::fs::remove_dir_all(&path)
+        } else {
+            std::fs::remove_file(&path)
+        };
+        match result {
+            Ok(()) => {
+                debug!("Pruned {}", path.display());
+                freed += size;
+            }
+            Err(e) => warn!("Failed to remove {}: {}", path.display(), e),
+        }
+    }
+    let count = index.entries.len();
+    index
+        .entries
+        .retain(|_, e| blob_dir(&e.digest).and_then(|dir| blob_in(&dir)).is_some());
+    if index.entries.len() != count {
+        write_index(&index)?;
+    }
+    Ok(freed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::install::test_server::{etag, TestServer};
+
+    fn body(len: usize, seed: u8) -> Vec<u8> {
+        (0..len)
+            .map(|i| (i as u8).wrapping_mul(31) ^ seed)
+            .collect()
+    }
+
+    fn part_file(name: &str) -> PathBuf {
+        crate::paths::use_temp_dirs();
+        let part = crate::paths::download_cache_dir()
+            .join("test")
+            .join(format!("{}.part", name));
+        std::fs::create_dir_all(part.parent().unwrap()).unwrap();
+        part
+    }
+
+    fn not_cancelled() -> AtomicBool {
+        AtomicBool::new(false)
+    }
+
+    #[test]
+    fn keeps_the_validator_of_a_new_download() {
+        let server = TestServer::start();
+        let data = body(1000, 1);
+        server.serve("/new.zip", data.clone());
+        let part = part_file("new");
+        let size = download(
+            &server.url("/new.zip"),
+            &part,
+            None,
+            &not_cancelled(),
+            |_, _| {},
+        )
+        .unwrap();
+        assert_eq!(size, 1000);
+        assert_eq!(std::fs::read(&part).unwrap(), data);
+        assert_eq!(
+            std::fs::read_to_string(validator_path(&part)).unwrap(),
+            etag(&data)
+        );
+    }
+
+    #[test]
+    fn resumes_with_if_range() {
+        let server = TestServer::start();
+        let data = body(300_000, 2);
+        server.serve("/resume.zip", data.clone());
+        let part = part_file("resume");
+        std::fs::write(&part, &data[..100_000]).unwrap();
+        std::fs::write(validator_path(&part), etag(&data)).unwrap();
+
+        let size = download(
+            &server.url("/resume.zip"),
+            &part,
+            Some(300_000),
+            &not_cancelled(),
+            |_, _| {},
+        )
+        .unwrap();
+        assert_eq!(size, 300_000);
+        assert_eq!(std::fs::read(&part).unwrap(), data);
+        let requests = server.requests("/resume.zip");
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].headers["range"], "bytes=100000-");
+        assert_eq!(requests[0].headers["if-range"], etag(&data));
+    }
+
+    #[test]
+    fn restarts_when_the_file_changed_on_the_server() {
+        let server = TestServer::start();
+        let old = body(2000, 3);
+        let new = body(3000, 4);
+        server.serve("/changed.zip", new.clone());
+        let part = part_file("changed");
+        std::fs::write(&part, &old[..1000]).unwrap();
+        std::fs::write(validator_path(&part), etag(&old)).unwrap();
+
+        let size = download(
+            &server.url("/changed.zip"),
+            &part,
+            None,
+            &not_cancelled(),
+            |_, _| {},
+        )
+        .unwrap();
+        assert_eq!(size, 3000);
+        assert_eq!(std::fs::read(&part).unwrap(), new);
+        assert_eq!(
+            std::fs::read_to_string(validator_path(&part)).unwrap(),
+            etag(&new)
+        );
+        assert_eq!(
+            server.requests("/changed.zip")[0].headers["if-range"],
+            etag(&old)
+        );
+    }
+
+    #[test]
+    fn restarts_without_a_validator() {
+        let server = TestServer::start();
+        let data = body(2000, 5);
+        server.serve("/unvalidated.zip", data.clone());
+        let part = part_file("unvalidated");
+        std::fs::write(&part, b"stale bytes").unwrap();
+
+        download(
+            &server.url("/unvalidated.zip"),
+            &part,
+            None,
+            &not_cancelled(),
+            |_, _| {},
+        )
+        .unwrap();
+        assert_eq!(std::fs::read(&part).unwrap(), data);
+        let requests = server.requests("/unvalidated.zip");
+        assert!(!requests[0].headers.contains_key("range"));
+    }
+
+    #[test]
+    fn rejects_a_digest_mismatch() {
+        crate::paths::use_temp_dirs();
+        let server = TestServer::start();
+        let data = body(1000, 6);
+        server.serve("/corrupt.zip", data);
+        let url = server.url("/corrupt.zip");
+        let wrong = format!("sha256:{}", "0".repeat(64));
+        let err = fetch(
+            &url,
+            "corrupt.zip",
+            Some(1000),
+            Some(&wrong),
+            &not_cancelled(),
+            |_, _, _| {},
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("Digest mismatch"), "{:#}", err);
+        let partial = crate::paths::download_cache_dir().join("partial");
+        let leftovers = std::fs::read_dir(partial)
+            .unwrap()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_name().to_string_lossy().contains("corrupt.zip"))
+            .count();
+        assert_eq!(leftovers, 0);
+        assert!(lookup(&url, None).is_none());
+    }
+
+    #[test]
+    fn serves_and_prunes_cached_downloads() {
+        crate::paths::use_temp_dirs();
+        let server = TestServer::start();
+        let data = body(5000, 7);
+        server.serve("/cached.zip", data.clone());
+        let url = server.url("/cached.zip");
+        let get = || {
+            fetch(
+                &url,
+                "cached.zip",
+                Some(5000),
+                None,
+                &not_cancelled(),
+                |_, _, _| {},
+            )
+        };
+        let path = get().unwrap();
+        assert_eq!(get().unwrap(), path);
+        assert_eq!(server.requests("/cached.zip").len(), 1);
+
+        let a_day_ago = SystemTime::now() - Duration::from_secs(24 * 60 * 60);
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(a_day_ago)
+            .unwrap();
+        let orphan = crate::paths::download_cache_dir()
+            .join("partial")
+            .join("orphan.part.validator");
+        std::fs::create_dir_all(orphan.parent().unwrap()).unwrap();
+        std::fs::write(&orphan, "\"x\"").unwrap();
+
+        let hour = Duration::from_secs(60 * 60);
+        assert!(prune_cache(hour, false).unwrap() >= 5000);
+        assert!(path.exists() && orphan.exists());
+        assert!(prune_cache(hour, true).unwrap() >= 5000);
+        assert!(!path.exists() && !orphan.exists());
+        assert!(!path.parent().unwrap().exists());
+        assert!(lookup(&url, None).is_none());
+    }
+}
@@ -0,0 +1,246 @@
+use crate::cdda::version::CddaVersion;
+use crate::prelude::*;
+use chrono::{DateTime, Local};
+
+pub mod build;
+pub mod download;
+pub mod pipeline;
+pub mod retention;
+pub mod rollback;
+#[cfg(test)]
+mod test_server;
+pub mod update;
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum ReleaseChannel {
+    Stable,
+    Experimental,
+    /// Not installed from a release (e.g. a game path set by hand, or a build from source).
+    Local,
+}
+
+impl ReleaseChannel {
+    /// Infers the channel from a release tag. Tags that are not CDDA versions count as stable.
+    pub fn from_tag(tag: &str) -> Self {
+        match tag.parse::<CddaVersion>() {
+            Ok(version) if version.is_experimental() => ReleaseChannel::Experimental,
+            _ => ReleaseChannel::Stable,
+        }
+    }
+}
+
+/// A game build installed on disk.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct GameInstall {
+    pub id: String,
+    /// Id of the `ReleaseSource` the build was installed from.
+    #[serde(default)]
+    pub source: Option<String>,
+    pub tag: Option<String>,
+    pub channel: ReleaseChannel,
+    pub platform: String,
+    pub install_dir: PathBuf,
+    /// Path to the game executable (or app bundle) inside `install_dir`.
+    pub game_path: PathBuf,
+    /// Version detected from the build's `VERSION.txt`, if any.
+    pub version: Option<String>,
+    /// Parsed from the tag, or from `version` for builds registered without one.
+    #[serde(default)]
+    pub cdda_version: Option<CddaVersion>,
+    pub installed_at: DateTime<Local>,
+    pub size_bytes: u64,
+    /// Kept by the cleanup of old builds (see `retention`).
+    #[serde(default)]
+    pub pinned: bool,
+}
+
+impl GameInstall {
+    /// Detects the game inside `install_dir` and builds a registry entry for it.
+    pub fn detect(
+        install_dir: &Path,
+        tag: Option<String>,
+        channel: ReleaseChannel,
+    ) -> Result<Self> {
+        ensure!(
+            install_dir.is_dir(),
+            "Install directory does not exist: {}",
+            install_dir.display()
+        );
+        let game_path = find_game_path(install_dir)
+            .ok_or_else(|| anyhow!("No game executable found in {}", install_dir.display()))?;
+        let installed_at = Local::now();
+        let version = detect_version(&game_path);
+        let cdda_version = tag
+            .as_deref()
+            .or(version.as_deref())
+            .and_then(|v| v.parse().ok());
+        Ok(Self {
+            id: format!(
+                "{}_{}",
+                tag.as_deref().unwrap_or("local"),
+                installed_at.format("%Y%m%d%H%M%S")
+            ),
+            source: None,
+            tag,
+            channel,
+            platform: crate::get_platform(),
+            install_dir: install_dir.to_path_buf(),
+            version,
+            cdda_version,
+            game_path,
+            installed_at,
+            size_bytes: dir_size(install_dir),
+            pinned: false,
+        })
+    }
+}
+
+fn game_file_name() -> &'static str {
+    #[cfg(target_os = "windows")]
+    {
+        "cataclysm-tiles.exe"
+    }
+    #[cfg(target_os = "macos")]
+    {
+        "Cataclysm.app"
+    }
+    #[cfg(target_os = "linux")]
+    {
+        "cataclysm-tiles"
+    }
+}
+
+/// Finds the game executable (or app bundle) in `dir`, looking a few levels deep
+/// since archives often contain a top-level directory.
+pub fn find_game_path(dir: &Path) -> Option<PathBuf> {
+    const MAX_DEPTH: usize = 3;
+    fn find(dir: &Path, depth: usize) -> Option<PathBuf> {
+        let candidate = dir.join(game_file_name());
+        if candidate.exists() {
+            return Some(candidate);
+        }
+        if depth == 0 {
+            return None;
+        }
+        std::fs::read_dir(dir)
+            .ok()?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.is_dir() && path.extension().map_or(true, |ext| ext != "app"))
+            .find_map(|path| find(&path, depth - 1))
+    }
+    find(dir, MAX_DEPTH)
+}
+
+/// Reads the build number (or the first line) from the `VERSION.txt` shipped with the game.
+pub fn detect_version(game_path: &Path) -> Option<String> {
+    let candidates = [
+        game_path.parent()?.join("VERSION.txt"),
+        game_path
+            .join("Contents")
+            .join("Resources")
+            .join("VERSION.txt"),
+    ];
+    let content = candidates
+        .iter()
+        .find_map(|path| std::fs::read_to_string(path).ok())?;
+    let build_number = content.lines().find_map(|line| {
+        line.strip_prefix("build number:")
+            .map(|v| v.trim().to_string())
+    });
+    build_number.or_else(|| content.lines().next().map(|l| l.trim().to_string()))
+}
+
+/// Total size of the files under `dir`. Symlinks are not followed.
+pub fn dir_size(dir: &Path) -> u64 {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return 0;
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| match entry.file_type() {
+            Ok(ty) if ty.is_dir() => dir_size(&entry.path()),
+            Ok(ty) if ty.is_file() => entry.metadata().map(|m| m.len()).unwrap_or(0),
+            _ => 0,
+        })
+        .sum()
+}
+
+pub mod commands {
+    use super::*;
+    use crate::cdda::session::SessionState;
+
+    #[derive(Debug, Serialize, Clone)]
+    pub struct GameInstallEntry {
+        #[serde(flatten)]
+        pub install: GameInstall,
+        /// Ids of the profiles using this install.
+        pub used_by: Vec<String>,
+    }
+
+    #[tauri::command]
+    pub fn list_game_installs(state: tauri::State<'_, AppState>) -> Vec<GameInstallEntry> {
+        let settings = state.get_settings().unwrap();
+        settings
+            .installs
+            .iter()
+            .map(|install| GameInstallEntry {
+                install: install.clone(),
+                used_by: settings.profiles_using_install(&install.id),
+            })
+            .collect()
+    }
+
+    /// Registers a game build extracted to `install_dir`.
+    /// If `channel` is omitted, it is inferred from `tag`.
+    /// If `profile_id` is given, the profile is switched to the new install.
+    #[tauri::command]
+    pub fn register_game_install(
+        state: tauri::State<'_, AppState>,
+        sessions: tauri::State<'_, SessionState>,
+        install_dir: String,
+        tag: Option<String>,
+        channel: Option<ReleaseChannel>,
+        profile_id: Option<String>,
+    ) -> Result<GameInstall, String> {
+        let channel = channel.unwrap_or_else(|| match &tag {
+            Some(tag) => ReleaseChannel::from_tag(tag),
+            None => ReleaseChannel::Local,
+        });
+        let install = GameInstall::detect(Path::new(&install_dir), tag, channel)
+            .map_err(|e| e.to_string())?;
+        state.update_settings(|settings| {
+            settings.register_install(install.clone());
+            if let Some(profile_id) = &profile_id {
+                sessions.ensure_not_running(profile_id)?;
+                settings.set_profile_install(profile_id, &install.id)?;
+            }
+            Ok(())
+        })?;
+        Ok(install)
+    }
+
+    /// Removes an install from the registry. Files on disk are kept.
+    #[tauri::command]
+    pub fn unregister_game_install(
+        state: tauri::State<'_, AppState>,
+        install_id: String,
+    ) -> Result<(), String> {
+        state.update_settings(|settings| settings.unregister_install(&install_id))
+    }
+
+    /// Switches a profile to another installed build. Refused while its game is running.
+    #[tauri::command]
+    pub fn set_profile_game_install(
+        state: tauri::State<'_, AppState>,
+        sessions: tauri::State<'_, SessionState>,
+        profile_id: String,
+        install_id: String,
+    ) -> Result<(), String> {
+        state.update_settings(|settings| {
+            sessions.ensure_not_running(&profile_id)?;
+            settings.set_profile_install(&profile_id, &install_id)
+        })
+    }
+}
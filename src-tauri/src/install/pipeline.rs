@@ -0,0 +1,431 @@
+//! Downloads a release asset, verifies it, extracts it into a versioned install directory
+//! and registers the result.
+use super::{download, GameInstall, ReleaseChannel};
+use crate::cdda::release::commands::ReleaseInfo;
+use crate::prelude::*;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+pub const INSTALL_PROGRESS: &str = "INSTALL_PROGRESS";
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum InstallStage {
+    Download,
+    Verify,
+    Extract,
+    Register,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct InstallProgress {
+    /// Id of the release source.
+    pub source: String,
+    pub tag_name: String,
+    pub stage: InstallStage,
+    pub progress: u64,
+    /// 0 if unknown.
+    pub total: u64,
+}
+
+/// Cancellation flags of the installs in progress, keyed by release source and tag.
+#[derive(Debug, Default)]
+pub struct InstallState {
+    running: std::sync::Mutex<HashMap<(String, String), Arc<AtomicBool>>>,
+}
+
+fn install_key(source: &str, tag_name: &str) -> (String, String) {
+    (source.to_string(), tag_name.to_string())
+}
+
+impl InstallState {
+    fn start(&self, source: &str, tag_name: &str) -> Result<Arc<AtomicBool>> {
+        let mut running = self.running.lock().unwrap();
+        let key = install_key(source, tag_name);
+        ensure!(
+            !running.contains_key(&key),
+            "{} of {} is already being installed",
+            tag_name,
+            source
+        );
+        let cancel = Arc::new(AtomicBool::new(false));
+        running.insert(key, cancel.clone());
+        Ok(cancel)
+    }
+
+    fn finish(&self, source: &str, tag_name: &str) {
+        self.running
+            .lock()
+            .unwrap()
+            .remove(&install_key(source, tag_name));
+    }
+
+    /// Requests cancellation. Returns false if no install of the tag is running.
+    pub fn cancel(&self, source: &str, tag_name: &str) -> bool {
+        match self
+            .running
+            .lock()
+            .unwrap()
+            .get(&install_key(source, tag_name))
+        {
+            Some(cancel) => {
+                cancel.store(true, Ordering::SeqCst);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+fn ensure_not_cancelled(cancel: &AtomicBool) -> Result<()> {
+    ensure!(!cancel.load(Ordering::SeqCst), "Install cancelled");
+    Ok(())
+}
+
+/// Tags are used as directory names.
+fn sanitize(tag_name: &str) -> String {
+    tag_name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c => c,
+        })
+        .collect()
+}
+
+fn extract(
+    archive: &Path,
+    target_dir: &Path,
+    cancel: &AtomicBool,
+    on_progress: impl FnMut(crate::dmg::Progress),
+) -> Result<()> {
+    let name = archive
+        .file_name()
+        .map(|n| n.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    std::fs::create_dir_all(target_dir)?;
+    if name.ends_with(".zip") {
+        crate::zip::unzip(archive, target_dir, cancel)?;
+    } else if crate::tarball::is_tarball(archive) {
+        crate::tarball::untar(archive, target_dir, cancel, on_progress)?;
+    } else if name.ends_with(".dmg") {
+        crate::dmg::mount_and_copy(on_progress, archive.to_path_buf(), target_dir.to_path_buf())?;
+    } else {
+        return Err(anyhow!("Unsupported archive: {}", name));
+    }
+    Ok(())
+}
+
+/// Downloads, verifies and extracts a release into `installs_dir()/<source>/<tag>`.
+/// The archive is kept in the download cache, and an interrupted download is resumed on retry.
+/// On failure or cancellation, the partially extracted files are removed.
+///
+/// The returned install is not registered yet.
+pub fn install_release(
+    release: &ReleaseInfo,
+    cancel: &AtomicBool,
+    mut on_progress: impl FnMut(InstallProgress),
+) -> Result<GameInstall> {
+    ensure!(
+        !release.download_url.is_empty(),
+        "No download URL for {}",
+        release.tag_name
+    );
+    let dir_name = sanitize(&release.tag_name);
+    let source_dir = sanitize(&release.source);
+    let install_dir = crate::paths::installs_dir()
+        .join(&source_dir)
+        .join(&dir_name);
+    ensure!(
+        !install_dir.exists(),
+        "Already installed: {}",
+        install_dir.display()
+    );
+    let file_name = release
+        .download_url
+        .rsplit('/')
+        .next()
+        .filter(|name| !name.is_empty())
+        .unwrap_or("archive");
+    let partial_dir = crate::paths::installs_dir()
+        .join(&source_dir)
+        .join(format!("{}.partial", dir_name));
+
+    let mut progress = |stage, progress, total| {
+        on_progress(InstallProgress {
+            source: release.source.clone(),
+            tag_name: release.tag_name.clone(),
+            stage,
+            progress,
+            total,
+        })
+    };
+
+    let result = (|| {
+        let archive = download::fetch(
+            &release.download_url,
+            file_name,
+            release.size,
+            release.digest.as_deref(),
+            cancel,
+            &mut progress,
+        )?;
+        ensure_not_cancelled(cancel)?;
+
+        progress(InstallStage::Extract, 0, 0);
+        if partial_dir.exists() {
+            std::fs::remove_dir_all(&partial_dir)?;
+        }
+        let extracted = extract(&archive, &partial_dir, cancel, |p| {
+            progress(InstallStage::Extract, p.progress, p.total)
+        });
+        ensure_not_cancelled(cancel)?;
+        extracted?;
+        std::fs::rename(&partial_dir, &install_dir)?;
+
+        progress(InstallStage::Register, 0, 0);
+        let mut install = GameInstall::detect(
+            &install_dir,
+            Some(release.tag_name.clone()),
+            ReleaseChannel::from_tag(&release.tag_name),
+        )?;
+        install.source = Some(release.source.clone());
+        Ok(install)
+    })();
+
+    if let Err(e) = &result {
+        warn!("Failed to install {}: {:#}", release.tag_name, e);
+        for dir in [&partial_dir, &install_dir] {
+            if dir.exists() {
+                std::fs::remove_dir_all(dir)
+                    .unwrap_or_else(|e| warn!("Failed to remove {}: {}", dir.display(), e));
+            }
+        }
+    }
+    result
+}
+
+pub mod commands {
+    use super::*;
+    use crate::cdda::session::SessionState;
+    use tauri::{AppHandle, Manager};
+
+    /// Downloads and installs a release, then registers it.
+    /// If `profile_id` is given, the profile is switched to the new install, which is refused
+    /// while its game is running. Progress is emitted as `INSTALL_PROGRESS`.
+    #[tauri::command]
+    pub async fn install_release(
+        app: AppHandle,
+        release: ReleaseInfo,
+        profile_id: Option<String>,
+    ) -> Result<GameInstall, String> {
+        tauri::async_runtime::spawn_blocking(move || {
+            let sessions = app.state::<SessionState>();
+            if let Some(profile_id) = &profile_id {
+                sessions
+                    .ensure_not_running(profile_id)
+                    .map_err(|e| e.to_string())?;
+            }
+            let installs = app.state::<InstallState>();
+            let cancel = installs
+                .start(&release.source, &release.tag_name)
+                .map_err(|e| e.to_string())?;
+            let result = super::install_release(&release, &cancel, |progress| {
+                app.emit(INSTALL_PROGRESS, progress)
+                    .unwrap_or_else(|e| warn!("Failed to emit install progress: {}", e));
+            });
+            installs.finish(&release.source, &release.tag_name);
+            let install = result.map_err(|e| format!("{:#}", e))?;
+
+            let state = app.state::<AppState>();
+            state.update_settings(|settings| {
+                settings.register_install(install.clone());
+                if let Some(profile_id) = &profile_id {
+                    sessions.ensure_not_running(profile_id)?;
+                    settings.set_profile_install(profile_id, &install.id)?;
+                }
+                Ok(())
+            })?;
+            info!(
+                "Installed {} to {}",
+                release.tag_name,
+                install.install_dir.display()
+            );
+            Ok(install)
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+
+    #[tauri::command]
+    pub fn cancel_install_release(
+        installs: tauri::State<'_, InstallState>,
+        source: String,
+        tag_name: String,
+    ) -> Result<(), String> {
+        match installs.cancel(&source, &tag_name) {
+            true => Ok(()),
+            false => Err(format!("{} of {} is not being installed", tag_name, source)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::install::test_server::TestServer;
+
+    /// A gzipped tarball with the game under a top-level directory, like the Linux releases.
+    fn game_tarball(build: &str) -> Vec<u8> {
+        let encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        let mut builder = tar::Builder::new(encoder);
+        let version = format!("build number: {}\n", build);
+        let files = [
+            (
+                format!("cdda/{}", crate::install::game_file_name()),
+                "#!/bin/sh\n",
+            ),
+            ("cdda/VERSION.txt".to_string(), version.as_str()),
+        ];
+        for (path, content) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o755);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, path, content.as_bytes())
+                .unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap()
+    }
+
+    /// Serves a game tarball for `build` and returns its release.
+    fn serve_release(server: &TestServer, build: &str) -> ReleaseInfo {
+        let path = format!("/releases/{}/cdda-linux.tar.gz", build);
+        server.serve(&path, game_tarball(build));
+        serde_json::from_value(serde_json::json!({
+            "source": "test",
+            "tag_name": format!("cdda-experimental-{}", build),
+            "browser_url": "",
+            "download_url": server.url(&path),
+        }))
+        .unwrap()
+    }
+
+    fn install_dirs(release: &ReleaseInfo) -> [PathBuf; 2] {
+        let source_dir = crate::paths::installs_dir().join(&release.source);
+        [
+            source_dir.join(&release.tag_name),
+            source_dir.join(format!("{}.partial", release.tag_name)),
+        ]
+    }
+
+    #[test]
+    fn installs_a_release_from_the_server() {
+        crate::paths::use_temp_dirs();
+        let server = TestServer::start();
+        let release = serve_release(&server, "2024-03-01-0613");
+        let [install_dir, partial_dir] = install_dirs(&release);
+
+        let mut stages = Vec::new();
+        let install = install_release(&release, &AtomicBool::new(false), |p| {
+            if stages.last() != Some(&p.stage) {
+                stages.push(p.stage);
+            }
+        })
+        .unwrap();
+
+        assert_eq!(
+            stages,
+            [
+                InstallStage::Download,
+                InstallStage::Verify,
+                InstallStage::Extract,
+                InstallStage::Register
+            ]
+        );
+        assert_eq!(install.install_dir, install_dir);
+        assert!(install.game_path.starts_with(&install_dir));
+        assert!(install.game_path.is_file());
+        assert!(!partial_dir.exists());
+        assert_eq!(install.source.as_deref(), Some("test"));
+        assert_eq!(install.channel, ReleaseChannel::Experimental);
+        assert_eq!(install.version.as_deref(), Some("2024-03-01-0613"));
+        assert!(install.cdda_version.is_some());
+
+        let again = install_release(&release, &AtomicBool::new(false), |_| {});
+        assert!(again.unwrap_err().to_string().contains("Already installed"));
+
+        // Reinstalled from the download cache.
+        std::fs::remove_dir_all(&install_dir).unwrap();
+        install_release(&release, &AtomicBool::new(false), |_| {}).unwrap();
+        assert_eq!(
+            server
+                .requests("/releases/2024-03-01-0613/cdda-linux.tar.gz")
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn rejects_a_size_mismatch() {
+        crate::paths::use_temp_dirs();
+        let server = TestServer::start();
+        let mut release = serve_release(&server, "2024-03-02-0613");
+        release.size = Some(1);
+
+        let result = install_release(&release, &AtomicBool::new(false), |_| {});
+
+        assert!(format!("{:#}", result.unwrap_err()).contains("Size mismatch"));
+        for dir in install_dirs(&release) {
+            assert!(!dir.exists(), "{} was left behind", dir.display());
+        }
+    }
+
+    #[test]
+    fn cancelling_removes_the_partial_install() {
+        crate::paths::use_temp_dirs();
+        let server = TestServer::start();
+        let release = serve_release(&server, "2024-03-03-0613");
+
+        let cancel = AtomicBool::new(false);
+        let result = install_release(&release, &cancel, |p| {
+            if p.stage == InstallStage::Extract {
+                cancel.store(true, Ordering::SeqCst);
+            }
+        });
+
+        assert_eq!(result.unwrap_err().to_string(), "Install cancelled");
+        for dir in install_dirs(&release) {
+            assert!(!dir.exists(), "{} was left behind", dir.display());
+        }
+    }
+
+    #[test]
+    fn cancelled_before_the_download() {
+        crate::paths::use_temp_dirs();
+        let server = TestServer::start();
+        let release = serve_release(&server, "2024-03-04-0613");
+
+        let result = install_release(&release, &AtomicBool::new(true), |_| {});
+
+        assert_eq!(result.unwrap_err().to_string(), "Install cancelled");
+        assert!(!install_dirs(&release)[0].exists());
+    }
+
+    #[test]
+    fn installs_are_keyed_by_source_and_tag() {
+        let installs = InstallState::default();
+        installs.start("cdda", "0.H").unwrap();
+        installs.start("fork", "0.H").unwrap();
+        assert!(installs.start("cdda", "0.H").is_err());
+
+        assert!(installs.cancel("fork", "0.H"));
+        assert!(!installs.cancel("other", "0.H"));
+
+        installs.finish("cdda", "0.H");
+        assert!(!installs.cancel("cdda", "0.H"));
+        assert!(installs.cancel("fork", "0.H"));
+    }
+}
@@ -0,0 +1,336 @@
+//! Cleanup of old game builds and of the download cache.
+//!
+//! Only builds installed by Catalyzer (under `installs_dir()`) are ever deleted. Builds registered
+//! from elsewhere and local builds are left alone.
+use super::download;
+use super::{GameInstall, ReleaseChannel};
+use crate::cdda::version::tag_key;
+use crate::prelude::*;
+use crate::profile::Settings;
+use std::collections::HashMap;
+
+fn default_keep_per_channel() -> usize {
+    3
+}
+
+fn default_keep_pinned() -> bool {
+    true
+}
+
+fn default_download_cache_days() -> u64 {
+    30
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct RetentionPolicy {
+    /// Newest builds kept per source and channel, besides those in use.
+    #[serde(default = "default_keep_per_channel")]
+    pub keep_per_channel: usize,
+    /// Never delete pinned builds.
+    #[serde(default = "default_keep_pinned")]
+    pub keep_pinned: bool,
+    /// Downloads unused for this many days are removed from the download cache.
+    #[serde(default = "default_download_cache_days")]
+    pub download_cache_days: u64,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            keep_per_channel: default_keep_per_channel(),
+            keep_pinned: default_keep_pinned(),
+            download_cache_days: default_download_cache_days(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum KeepReason {
+    /// Among the newest `keep_per_channel` builds.
+    Recent,
+    UsedByProfile,
+    /// A profile can roll back to it.
+    RollbackTarget,
+    Pinned,
+    /// Not installed by Catalyzer, or not from a release.
+    Unmanaged,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct CleanupPlan {
+    pub remove: Vec<GameInstall>,
+    pub keep: Vec<(GameInstall, KeepReason)>,
+    /// Disk space freed by removing the builds in `remove`.
+    pub freed_bytes: u64,
+    /// Disk space freed by pruning the download cache.
+    pub cache_freed_bytes: u64,
+}
+
+fn prune_download_cache(policy: &RetentionPolicy, apply: bool) -> u64 {
+    let max_age =
+        std::time::Duration::from_secs(policy.download_cache_days.saturating_mul(24 * 60 * 60));
+    download::prune_cache(max_age, apply).unwrap_or_else(|e| {
+        warn!("Failed to prune the download cache: {:#}", e);
+        0
+    })
+}
+
+/// Decides which builds and downloads the policy removes. Nothing is deleted.
+pub fn plan(settings: &Settings, policy: &RetentionPolicy) -> CleanupPlan {
+    let managed_dir = crate::paths::installs_dir();
+    let mut groups: HashMap<(Option<String>, ReleaseChannel), Vec<&GameInstall>> = HashMap::new();
+    let mut keep = Vec::new();
+    for install in &settings.installs {
+        if install.channel == ReleaseChannel::Local
+            || !install.install_dir.starts_with(&managed_dir)
+        {
+            keep.push((install.clone(), KeepReason::Unmanaged));
+            continue;
+        }
+        groups
+            .entry((install.source.clone(), install.channel))
+            .or_default()
+            .push(install);
+    }
+
+    let mut remove = Vec::new();
+    for mut installs in groups.into_values() {
+        // Newest first
+        installs.sort_by(|a, b| {
+            let a = (a.tag.as_deref().map(tag_key), a.installed_at);
+            let b = (b.tag.as_deref().map(tag_key), b.installed_at);
+            b.cmp(&a)
+        });
+        for (i, install) in installs.into_iter().enumerate() {
+            let reason = if !settings.profiles_using_install(&install.id).is_empty() {
+                Some(KeepReason::UsedByProfile)
+            } else if !settings
+                .profiles_with_previous_install(&install.id)
+                .is_empty()
+            {
+                Some(KeepReason::RollbackTarget)
+            } else if install.pinned && policy.keep_pinned {
+                Some(KeepReason::Pinned)
+            } else if i < policy.keep_per_channel {
+                Some(KeepReason::Recent)
+            } else {
+                None
+            };
+            match reason {
+                Some(reason) => keep.push((install.clone(), reason)),
+                None => remove.push(install.clone()),
+            }
+        }
+    }
+    CleanupPlan {
+        freed_bytes: remove.iter().map(|i| i.size_bytes).sum(),
+        cache_freed_bytes: prune_download_cache(policy, false),
+        remove,
+        keep,
+    }
+}
+
+/// Deletes the builds of a fresh plan and unregisters them, and prunes the download cache.
+/// Returns the plan, with the builds that could not be deleted moved out of `remove`.
+pub fn apply(settings: &mut Settings, policy: &RetentionPolicy) -> CleanupPlan {
+    let mut plan = plan(settings, policy);
+    let mut removed = Vec::new();
+    for install in std::mem::take(&mut plan.remove) {
+        info!(
+            "Removing {} ({})",
+            install.id,
+            install.install_dir.display()
+        );
+        let result = crate::files::remove_dir_all(&install.install_dir, None)
+            .map_err(anyhow::Error::from)
+            .and_then(|_| settings.unregister_install(&install.id));
+        match result {
+            Ok(()) => removed.push(install),
+            Err(e) => warn!("Failed to remove {}: {:#}", install.id, e),
+        }
+    }
+    plan.freed_bytes = removed.iter().map(|i| i.size_bytes).sum();
+    plan.remove = removed;
+    plan.cache_freed_bytes = prune_download_cache(policy, true);
+    plan
+}
+
+pub mod commands {
+    use super::*;
+
+    /// Reports what the retention policy would delete. `policy` defaults to the configured one.
+    #[tauri::command]
+    pub fn plan_install_cleanup(
+        state: tauri::State<'_, AppState>,
+        policy: Option<RetentionPolicy>,
+    ) -> CleanupPlan {
+        let settings = state.get_settings().unwrap();
+        let policy = policy.unwrap_or_else(|| settings.retention.clone());
+        plan(&settings, &policy)
+    }
+
+    /// Deletes the builds the retention policy does not keep.
+    #[tauri::command]
+    pub async fn apply_install_cleanup(
+        app: tauri::AppHandle,
+        policy: Option<RetentionPolicy>,
+    ) -> Result<CleanupPlan, String> {
+        use tauri::Manager;
+        tauri::async_runtime::spawn_blocking(move || {
+            app.state::<AppState>().update_settings(|settings| {
+                let policy = policy.unwrap_or_else(|| settings.retention.clone());
+                Ok(apply(settings, &policy))
+            })
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+
+    #[tauri::command]
+    pub fn set_retention_policy(
+        state: tauri::State<'_, AppState>,
+        policy: RetentionPolicy,
+    ) -> Result<(), String> {
+        state.update_settings(|settings| {
+            settings.set_retention_policy(policy);
+            Ok(())
+        })
+    }
+
+    /// Pinned builds are kept by the cleanup.
+    #[tauri::command]
+    pub fn set_game_install_pinned(
+        state: tauri::State<'_, AppState>,
+        install_id: String,
+        pinned: bool,
+    ) -> Result<(), String> {
+        state.update_settings(|settings| settings.set_install_pinned(&install_id, pinned))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ReleaseChannel::*;
+
+    fn install(id: &str, tag: &str, channel: ReleaseChannel) -> GameInstall {
+        let install_dir = crate::paths::installs_dir().join(id);
+        GameInstall {
+            id: id.to_string(),
+            source: Some("cdda".to_string()),
+            tag: Some(tag.to_string()),
+            channel,
+            platform: crate::get_platform(),
+            game_path: install_dir.join("cataclysm-tiles"),
+            install_dir,
+            version: None,
+            cdda_version: tag.parse().ok(),
+            installed_at: chrono::Local::now(),
+            size_bytes: 100,
+            pinned: false,
+        }
+    }
+
+    fn settings(installs: Vec<GameInstall>) -> Settings {
+        Settings {
+            installs,
+            ..Settings::default()
+        }
+    }
+
+    fn removed(plan: &CleanupPlan) -> Vec<&str> {
+        let mut ids = plan
+            .remove
+            .iter()
+            .map(|i| i.id.as_str())
+            .collect::<Vec<_>>();
+        ids.sort();
+        ids
+    }
+
+    fn reason(plan: &CleanupPlan, id: &str) -> Option<KeepReason> {
+        plan.keep
+            .iter()
+            .find(|(install, _)| install.id == id)
+            .map(|(_, reason)| *reason)
+    }
+
+    #[test]
+    fn keeps_the_newest_builds_per_source_and_channel() {
+        crate::paths::use_temp_dirs();
+        let mut other = install("other", "cdda-experimental-2024-01-01-0000", Experimental);
+        other.source = Some("mirror".to_string());
+        let settings = settings(vec![
+            install("e1", "cdda-experimental-2024-01-01-0000", Experimental),
+            install("e3", "cdda-experimental-2024-03-01-0000", Experimental),
+            install("e2", "cdda-experimental-2024-02-01-0000", Experimental),
+            install("s1", "0.G", Stable),
+            install("s2", "0.H", Stable),
+            other,
+        ]);
+        let policy = RetentionPolicy {
+            keep_per_channel: 2,
+            ..Default::default()
+        };
+        let plan = plan(&settings, &policy);
+        assert_eq!(removed(&plan), ["e1"]);
+        assert_eq!(plan.freed_bytes, 100);
+        for id in ["e2", "e3", "s1", "s2", "other"] {
+            assert_eq!(reason(&plan, id), Some(KeepReason::Recent), "{}", id);
+        }
+
+        let policy = RetentionPolicy {
+            keep_per_channel: 1,
+            ..Default::default()
+        };
+        assert_eq!(
+            removed(&super::plan(&settings, &policy)),
+            ["e1", "e2", "s1"]
+        );
+    }
+
+    #[test]
+    fn keeps_builds_in_use_pinned_and_unmanaged() {
+        crate::paths::use_temp_dirs();
+        let mut pinned = install("pinned", "cdda-experimental-2024-01-02-0000", Experimental);
+        pinned.pinned = true;
+        let mut elsewhere = install(
+            "elsewhere",
+            "cdda-experimental-2023-01-01-0000",
+            Experimental,
+        );
+        elsewhere.install_dir = PathBuf::from("/opt/cdda");
+        let mut settings = settings(vec![
+            install("used", "cdda-experimental-2024-01-01-0000", Experimental),
+            pinned,
+            install("old", "cdda-experimental-2024-01-03-0000", Experimental),
+            install("newest", "cdda-experimental-2024-01-04-0000", Experimental),
+            install("local", "local", Local),
+            elsewhere,
+        ]);
+        let profile_id = settings.profiles[0].get_id().to_string();
+        settings.set_profile_install(&profile_id, "used").unwrap();
+
+        let policy = RetentionPolicy {
+            keep_per_channel: 1,
+            ..Default::default()
+        };
+        let plan = plan(&settings, &policy);
+        assert_eq!(removed(&plan), ["old"]);
+        assert_eq!(reason(&plan, "newest"), Some(KeepReason::Recent));
+        assert_eq!(reason(&plan, "used"), Some(KeepReason::UsedByProfile));
+        assert_eq!(reason(&plan, "pinned"), Some(KeepReason::Pinned));
+        assert_eq!(reason(&plan, "local"), Some(KeepReason::Unmanaged));
+        assert_eq!(reason(&plan, "elsewhere"), Some(KeepReason::Unmanaged));
+
+        let unpinned = RetentionPolicy {
+            keep_pinned: false,
+            ..policy
+        };
+        assert_eq!(
+            removed(&super::plan(&settings, &unpinned)),
+            ["old", "pinned"]
+        );
+    }
+}
@@ -0,0 +1,388 @@
+//! Rolling a profile back to the game build it played before the last switch.
+//!
+//! When a profile switches builds, the previous build is remembered and the saves are backed up,
+//! so that a bad build can be undone. With `auto_rollback`, a crash on the first launch of the new
+//! build rolls back automatically.
+use crate::cdda::session::{game_build, GameSession};
+use crate::prelude::*;
+use crate::profile::{Profile, Settings};
+use chrono::{DateTime, Local};
+
+/// Event emitted when a profile was rolled back automatically. Payload: [`RolledBack`]
+pub const ROLLED_BACK: &str = "ROLLED_BACK";
+
+/// The build a profile played before switching to the current one.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct PreviousBuild {
+    /// `None` if the game path was set by hand.
+    pub install_id: Option<String>,
+    pub game_path: Option<PathBuf>,
+    pub switched_at: DateTime<Local>,
+    /// Copy of the save directory taken at the switch.
+    #[serde(default)]
+    pub save_backup: Option<PathBuf>,
+    /// The current build has been launched since the switch.
+    #[serde(default)]
+    pub launched: bool,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct RolledBack {
+    pub profile_id: String,
+    /// The build rolled back to.
+    pub build: PreviousBuild,
+}
+
+/// Copies the profile's saves to `save_backup_dir()/<profile id>/<time>`.
+/// Returns `None` if there is nothing to back up.
+pub fn backup_saves(profile: &Profile) -> Result<Option<PathBuf>> {
+    let save_dir = profile.get_user_data_paths().save_dir();
+    let is_empty = std::fs::read_dir(save_dir)
+        .map(|mut entries| entries.next().is_none())
+        .unwrap_or(true);
+    if is_empty {
+        return Ok(None);
+    }
+    let backup_dir = crate::paths::save_backup_dir().join(profile.get_id());
+    let stamp = Local::now().format("%Y%m%d%H%M%S").to_string();
+    // A switch followed by a rollback may take two backups within the same second.
+    let backup = std::iter::once(backup_dir.join(&stamp))
+        .chain((1..).map(|n| backup_dir.join(format!("{}-{}", stamp, n))))
+        .find(|path| !path.exists())
+        .unwrap();
+    crate::files::copy_dir_all(save_dir, &backup, None)
+        .with_context(|| format!("Failed to back up saves to {}", backup.display()))?;
+    info!(
+        "Backed up saves of {} to {}",
+        profile.get_name(),
+        backup.display()
+    );
+    Ok(Some(backup))
+}
+
+/// Replaces the profile's saves with a backup.
+/// Returns the backup of the replaced saves, if there were any.
+fn restore_saves(profile: &Profile, backup: &Path) -> Result<Option<PathBuf>> {
+    ensure!(
+        backup.is_dir(),
+        "Save backup not found: {}",
+        backup.display()
+    );
+    let save_dir = profile.get_user_data_paths().save_dir();
+    let replaced = backup_saves(profile)?;
+    if save_dir.exists() {
+        crate::files::remove_dir_all(save_dir, None)?;
+    }
+    crate::files::copy_dir_all(backup, save_dir, None)?;
+    info!(
+        "Restored saves of {} from {}",
+        profile.get_name(),
+        backup.display()
+    );
+    Ok(replaced)
+}
+
+/// Removes a backup no longer referenced by the profile.
+pub fn remove_backup(backup: &Path) {
+    if backup.starts_with(crate::paths::save_backup_dir()) && backup.exists() {
+        crate::files::remove_dir_all(backup, None)
+            .unwrap_or_else(|e| warn!("Failed to remove {}: {}", backup.display(), e));
+    }
+}
+
+/// Points the profile back to its previous build. The build rolled back from becomes the
+/// previous one, so the rollback can be undone.
+///
+/// With `restore`, the saves backed up at the switch replace the current ones.
+pub fn rollback(settings: &mut Settings, profile_id: &str, restore: bool) -> Result<PreviousBuild> {
+    let profile = settings
+        .profiles
+        .iter()
+        .find(|p| p.get_id() == profile_id)
+        .ok_or_else(|| anyhow!("Profile not found: {}", profile_id))?
+        .clone();
+    let previous = profile
+        .get_previous_build()
+        .cloned()
+        .ok_or_else(|| anyhow!("{} has no previous build", profile.get_name()))?;
+    if let Some(install_id) = &previous.install_id {
+        ensure!(
+            settings.get_install(install_id).is_some(),
+            "The previous build was removed: {}",
+            install_id
+        );
+    }
+    let replaced_saves = if restore {
+        let backup = previous
+            .save_backup
+            .as_ref()
+            .ok_or_else(|| anyhow!("No save backup was taken at the switch"))?;
+        restore_saves(&profile, backup)?
+    } else {
+        None
+    };
+    settings.swap_previous_build(profile_id, replaced_saves)?;
+    info!(
+        "Rolled back {} to {}",
+        profile.get_name(),
+        previous
+            .install_id
+            .as_deref()
+            .or(previous.game_path.as_ref().and_then(|p| p.to_str()))
+            .unwrap_or("-")
+    );
+    Ok(previous)
+}
+
+/// Called when a game session ends. Marks the first launch of a new build, and rolls back
+/// if it crashed and the profile opted in to `auto_rollback`.
+/// Sessions of a build the profile no longer plays are ignored.
+///
+/// Returns the build rolled back to, if any.
+pub fn on_session_end(settings: &mut Settings, session: &GameSession) -> Option<PreviousBuild> {
+    let profile = settings
+        .profiles
+        .iter()
+        .find(|p| p.get_id() == session.profile_id)?;
+    if session.game_build != game_build(profile) {
+        return None;
+    }
+    let previous = profile.get_previous_build()?;
+    if previous.launched {
+        return None;
+    }
+    let auto_rollback = profile.get_auto_rollback();
+    settings
+        .mark_build_launched(&session.profile_id)
+        .unwrap_or_else(|e| warn!("{}", e));
+    if session.success == Some(true) || !auto_rollback {
+        return None;
+    }
+    warn!(
+        "The first launch of a new build crashed (exit code: {:?}). Rolling back",
+        session.exit_code
+    );
+    rollback(settings, &session.profile_id, false)
+        .map_err(|e| warn!("Auto-rollback failed: {:#}", e))
+        .ok()
+}
+
+pub mod commands {
+    use super::*;
+
+    /// Points the profile back to its previous build.
+    /// If `restore_saves` is true, the saves backed up at the switch are restored.
+    /// Refused while the game of the profile is running.
+    #[tauri::command]
+    pub async fn rollback_profile_game(
+        app: tauri::AppHandle,
+        profile_id: String,
+        restore_saves: Option<bool>,
+    ) -> Result<PreviousBuild, String> {
+        use tauri::Manager;
+        if app
+            .state::<crate::cdda::session::SessionState>()
+            .get_active(&profile_id)
+            .is_some()
+        {
+            return Err("Cannot roll back while the game is running".to_string());
+        }
+        tauri::async_runtime::spawn_blocking(move || {
+            app.state::<AppState>().update_settings(|settings| {
+                rollback(settings, &profile_id, restore_saves.unwrap_or(false))
+            })
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+
+    /// Opts in to rolling back automatically if a new build crashes on its first launch.
+    #[tauri::command]
+    pub fn set_profile_auto_rollback(
+        state: tauri::State<'_, AppState>,
+        profile_id: String,
+        enabled: bool,
+    ) -> Result<(), String> {
+        state.update_settings(|settings| settings.set_auto_rollback(&profile_id, enabled))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::install::{GameInstall, ReleaseChannel};
+
+    fn install(id: &str) -> GameInstall {
+        let install_dir = crate::paths::installs_dir().join(id);
+        GameInstall {
+            id: id.to_string(),
+            source: Some("cdda".to_string()),
+            tag: Some(id.to_string()),
+            channel: ReleaseChannel::Experimental,
+            platform: crate::get_platform(),
+            game_path: install_dir.join("cataclysm-tiles"),
+            install_dir,
+            version: None,
+            cdda_version: None,
+            installed_at: Local::now(),
+            size_bytes: 100,
+            pinned: false,
+        }
+    }
+
+    /// A profile playing `old`, switched to `new`. Each test uses its own profile directory.
+    fn switched(profile_id: &str, old: &str, new: &str) -> Settings {
+        let mut settings = Settings {
+            profiles: vec![Profile::new(
+                profile_id.to_string(),
+                "test".to_string(),
+                None,
+            )],
+            installs: vec![install(old), install(new)],
+            ..Settings::default()
+        };
+        settings.set_profile_install(profile_id, old).unwrap();
+        write_save(&settings, profile_id, old);
+        settings.set_profile_install(profile_id, new).unwrap();
+        settings
+    }
+
+    fn profile<'a>(settings: &'a Settings, profile_id: &str) -> &'a Profile {
+        settings
+            .profiles
+            .iter()
+            .find(|p| p.get_id() == profile_id)
+            .unwrap()
+    }
+
+    fn write_save(settings: &Settings, profile_id: &str, content: &str) {
+        let world = profile(settings, profile_id)
+            .get_user_data_paths()
+            .save_dir()
+            .join("world");
+        std::fs::create_dir_all(&world).unwrap();
+        std::fs::write(world.join("save.txt"), content).unwrap();
+    }
+
+    fn read_save(dir: &Path) -> String {
+        std::fs::read_to_string(dir.join("world").join("save.txt")).unwrap()
+    }
+
+    fn session_end(settings: &Settings, profile_id: &str, success: bool) -> GameSession {
+        let mut session = GameSession::new(profile(settings, profile_id), 1);
+        session.exit_code = Some(if success { 0 } else { 1 });
+        session.success = Some(success);
+        session
+    }
+
+    #[test]
+    fn backs_up_saves_on_switch() {
+        crate::paths::use_temp_dirs();
+        let mut settings = switched("rollback-backup", "a", "b");
+        settings.installs.push(install("c"));
+        let first = profile(&settings, "rollback-backup")
+            .get_previous_build()
+            .unwrap()
+            .clone();
+        assert_eq!(first.install_id.as_deref(), Some("a"));
+        assert!(!first.launched);
+        let first_backup = first.save_backup.unwrap();
+        assert_eq!(read_save(&first_backup), "a");
+
+        let session = session_end(&settings, "rollback-backup", true);
+        assert!(on_session_end(&mut settings, &session).is_none());
+        let previous = profile(&settings, "rollback-backup")
+            .get_previous_build()
+            .unwrap();
+        assert!(previous.launched);
+
+        // Only the latest switch is kept, with its own backup.
+        write_save(&settings, "rollback-backup", "b");
+        settings
+            .set_profile_install("rollback-backup", "c")
+            .unwrap();
+        let second = profile(&settings, "rollback-backup")
+            .get_previous_build()
+            .unwrap();
+        assert_eq!(second.install_id.as_deref(), Some("b"));
+        assert!(!second.launched);
+        assert_eq!(read_save(second.save_backup.as_ref().unwrap()), "b");
+        assert!(!first_backup.exists());
+    }
+
+    #[test]
+    fn rolls_back_when_the_first_launch_crashes() {
+        crate::paths::use_temp_dirs();
+        let mut settings = switched("rollback-crash", "a", "b");
+        settings.set_auto_rollback("rollback-crash", true).unwrap();
+
+        let session = session_end(&settings, "rollback-crash", false);
+        let build = on_session_end(&mut settings, &session).unwrap();
+
+        assert_eq!(build.install_id.as_deref(), Some("a"));
+        let profile = profile(&settings, "rollback-crash");
+        assert_eq!(profile.get_install_id(), Some("a"));
+        let previous = profile.get_previous_build().unwrap();
+        assert_eq!(previous.install_id.as_deref(), Some("b"));
+        assert!(previous.launched);
+        // The build rolled back to has been played before.
+        let session = session_end(&settings, "rollback-crash", false);
+        assert!(on_session_end(&mut settings, &session).is_none());
+    }
+
+    #[test]
+    fn rolls_back_only_on_opt_in_and_for_the_current_build() {
+        crate::paths::use_temp_dirs();
+        let mut settings = switched("rollback-opt-in", "a", "b");
+        let stale = session_end(&settings, "rollback-opt-in", false);
+        settings.installs.push(install("c"));
+        settings
+            .set_profile_install("rollback-opt-in", "c")
+            .unwrap();
+        settings.set_auto_rollback("rollback-opt-in", true).unwrap();
+
+        // A session of the build switched away from does not count as the first launch.
+        assert!(on_session_end(&mut settings, &stale).is_none());
+        let previous = profile(&settings, "rollback-opt-in").get_previous_build();
+        assert!(!previous.unwrap().launched);
+
+        settings
+            .set_auto_rollback("rollback-opt-in", false)
+            .unwrap();
+        let session = session_end(&settings, "rollback-opt-in", false);
+        assert!(on_session_end(&mut settings, &session).is_none());
+        let profile = profile(&settings, "rollback-opt-in");
+        assert_eq!(profile.get_install_id(), Some("c"));
+        assert!(profile.get_previous_build().unwrap().launched);
+    }
+
+    #[test]
+    fn rollback_can_be_undone() {
+        crate::paths::use_temp_dirs();
+        let mut settings = switched("rollback-undo", "a", "b");
+        write_save(&settings, "rollback-undo", "b");
+        let save_dir = profile(&settings, "rollback-undo")
+            .get_user_data_paths()
+            .save_dir()
+            .to_path_buf();
+
+        let build = rollback(&mut settings, "rollback-undo", true).unwrap();
+        assert_eq!(build.install_id.as_deref(), Some("a"));
+        assert_eq!(read_save(&save_dir), "a");
+        let previous = profile(&settings, "rollback-undo")
+            .get_previous_build()
+            .unwrap();
+        assert_eq!(previous.install_id.as_deref(), Some("b"));
+        assert_eq!(read_save(previous.save_backup.as_ref().unwrap()), "b");
+
+        let build = rollback(&mut settings, "rollback-undo", true).unwrap();
+        assert_eq!(build.install_id.as_deref(), Some("b"));
+        assert_eq!(read_save(&save_dir), "b");
+        let profile = profile(&settings, "rollback-undo");
+        assert_eq!(profile.get_install_id(), Some("b"));
+        assert_eq!(
+            profile.get_previous_build().unwrap().install_id.as_deref(),
+            Some("a")
+        );
+    }
+}
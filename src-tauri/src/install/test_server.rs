@@ -0,0 +1,151 @@
+//! A minimal HTTP server standing in for GitHub and mirrors in tests.
+//!
+//! Serves files from memory with an `ETag`, and honours `Range` and `If-Range` like GitHub's
+//! asset storage does.
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub path: String,
+    /// Header names are lowercase.
+    pub headers: HashMap<String, String>,
+}
+
+#[derive(Default)]
+struct Files {
+    bodies: HashMap<String, Vec<u8>>,
+    requests: Vec<Request>,
+}
+
+pub struct TestServer {
+    addr: SocketAddr,
+    files: Arc<Mutex<Files>>,
+}
+
+impl TestServer {
+    pub fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let files = Arc::new(Mutex::new(Files::default()));
+        let shared = files.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                handle(stream, &shared).ok();
+            }
+        });
+        Self { addr, files }
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("http://{}{}", self.addr, path)
+    }
+
+    /// Serves `body` at `path`, replacing what was served there.
+    pub fn serve(&self, path: &str, body: Vec<u8>) {
+        self.files
+            .lock()
+            .unwrap()
+            .bodies
+            .insert(path.to_string(), body);
+    }
+
+    /// The requests received for `path`, oldest first.
+    pub fn requests(&self, path: &str) -> Vec<Request> {
+        let files = self.files.lock().unwrap();
+        files
+            .requests
+            .iter()
+            .filter(|r| r.path == path)
+            .cloned()
+            .collect()
+    }
+}
+
+pub fn etag(body: &[u8]) -> String {
+    let digest = Sha256::digest(body);
+    let hex = digest[..8]
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<String>();
+    format!("\"{}\"", hex)
+}
+
+fn handle(stream: TcpStream, files: &Mutex<Files>) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let path = line.split_whitespace().nth(1).unwrap_or("/").to_string();
+    let mut headers = HashMap::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.insert(name.trim().to_lowercase(), value.trim().to_string());
+        }
+    }
+    let body = {
+        let mut files = files.lock().unwrap();
+        files.requests.push(Request {
+            path: path.clone(),
+            headers: headers.clone(),
+        });
+        files.bodies.get(&path).cloned()
+    };
+    let Some(body) = body else {
+        return respond(stream, "404 Not Found", &[], &[]);
+    };
+    let etag = etag(&body);
+    let len = body.len();
+    let range_start = headers
+        .get("range")
+        .and_then(|r| r.strip_prefix("bytes="))
+        .and_then(|r| r.strip_suffix('-'))
+        .and_then(|start| start.parse::<usize>().ok())
+        .filter(|_| headers.get("if-range").map_or(true, |v| *v == etag));
+    match range_start {
+        Some(start) if start >= len => respond(
+            stream,
+            "416 Range Not Satisfiable",
+            &[("Content-Range", format!("bytes */{}", len))],
+            &[],
+        ),
+        Some(start) => respond(
+            stream,
+            "206 Partial Content",
+            &[
+                ("ETag", etag),
+                (
+                    "Content-Range",
+                    format!("bytes {}-{}/{}", start, len - 1, len),
+                ),
+            ],
+            &body[start..],
+        ),
+        None => respond(stream, "200 OK", &[("ETag", etag)], &body),
+    }
+}
+
+fn respond(
+    mut stream: TcpStream,
+    status: &str,
+    headers: &[(&str, String)],
+    body: &[u8],
+) -> std::io::Result<()> {
+    write!(stream, "HTTP/1.1 {}\r\n", status)?;
+    for (name, value) in headers {
+        write!(stream, "{}: {}\r\n", name, value)?;
+    }
+    write!(
+        stream,
+        "Content-Length: {}\r\nConnection: close\r\n\r\n",
+        body.len()
+    )?;
+    stream.write_all(body)?;
+    stream.flush()
+}
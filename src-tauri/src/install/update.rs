@@ -0,0 +1,365 @@
+//! Background checks for newer builds of the installed games.
+//!
+//! Each profile follows a channel (by default the channel of its install). The newest tag of each
+//! (source, channel) pair is looked up once per check through the release cache, so checks cost
+//! no more requests than browsing the release list.
+use super::ReleaseChannel;
+use crate::cdda::release::commands::ReleaseInfo;
+use crate::cdda::release::github::RateLimited;
+use crate::cdda::release::source::DEFAULT_SOURCE_ID;
+use crate::cdda::version::cmp_tags;
+use crate::prelude::*;
+use crate::profile::Settings;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+
+pub const UPDATES_AVAILABLE: &str = "UPDATES_AVAILABLE";
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_interval_minutes() -> u64 {
+    6 * 60
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct UpdateCheckSettings {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(default = "default_interval_minutes")]
+    pub interval_minutes: u64,
+}
+
+impl Default for UpdateCheckSettings {
+    fn default() -> Self {
+        Self {
+            enabled: default_enabled(),
+            interval_minutes: default_interval_minutes(),
+        }
+    }
+}
+
+/// A newer build for a profile.
+#[derive(Debug, Serialize, Clone)]
+pub struct AvailableUpdate {
+    pub profile_id: String,
+    pub profile_name: String,
+    pub install_id: String,
+    pub current_tag: String,
+    pub latest: ReleaseInfo,
+}
+
+impl AvailableUpdate {
+    fn key(&self) -> (&str, &str, &str) {
+        (&self.profile_id, &self.latest.source, &self.latest.tag_name)
+    }
+}
+
+/// The outcome of a check.
+#[derive(Debug, Default)]
+pub struct UpdateCheck {
+    pub updates: Vec<AvailableUpdate>,
+    /// The API quota ran out. No check should be made before then.
+    pub rate_limited_until: Option<DateTime<Utc>>,
+}
+
+/// Whether `newest_tag`, the newest of `channel`, updates a build of `current_channel`.
+///
+/// Versions of different channels don't compare by meaning, so a profile switched to another
+/// channel is offered the newest build of that channel, whatever its version.
+fn offers_update(
+    current_tag: &str,
+    current_channel: ReleaseChannel,
+    channel: ReleaseChannel,
+    newest_tag: &str,
+) -> bool {
+    if channel != current_channel {
+        return newest_tag != current_tag;
+    }
+    cmp_tags(newest_tag, current_tag).is_gt()
+}
+
+/// Compares each profile's install with the newest release of its channel.
+///
+/// Sources that are offline (served only from a stale cache) or rate-limited are skipped.
+pub fn check_updates(settings: &Settings) -> UpdateCheck {
+    let mut check = UpdateCheck::default();
+    let mut latest: HashMap<(String, ReleaseChannel), Option<ReleaseInfo>> = HashMap::new();
+    for profile in &settings.profiles {
+        let Some(install) = profile
+            .get_install_id()
+            .and_then(|id| settings.get_install(id))
+        else {
+            continue;
+        };
+        let Some(current_tag) = &install.tag else {
+            continue;
+        };
+        // Builds registered by hand have no source. Assume CDDA.
+        let source_id = install.source.as_deref().unwrap_or(DEFAULT_SOURCE_ID);
+        let channel = profile.get_update_channel().unwrap_or(install.channel);
+        if channel == ReleaseChannel::Local {
+            continue;
+        }
+
+        let newest = latest
+            .entry((source_id.to_string(), channel))
+            .or_insert_with(|| {
+                if check.rate_limited_until.is_some() {
+                    return None;
+                }
+                let source = settings
+                    .release_source(Some(source_id))
+                    .map_err(|e| warn!("Update check skipped: {:#}", e))
+                    .ok()?;
+                match source.list(channel, 1, &settings.asset_preference) {
+                    Ok(list) if list.stale => {
+                        debug!("{} is unreachable. Update check skipped", source_id);
+                        None
+                    }
+                    Ok(mut list) => list.releases.pop(),
+                    Err(e) => {
+                        match e.downcast_ref::<RateLimited>() {
+                            Some(limited) => check.rate_limited_until = Some(limited.until),
+                            None => warn!("Update check of {} failed: {:#}", source_id, e),
+                        }
+                        None
+                    }
+                }
+            });
+        let Some(newest) = newest.as_ref() else {
+            continue;
+        };
+        if offers_update(current_tag, install.channel, channel, &newest.tag_name)
+            && !newest.download_url.is_empty()
+        {
+            check.updates.push(AvailableUpdate {
+                profile_id: profile.get_id().to_string(),
+                profile_name: profile.get_name().to_string(),
+                install_id: install.id.clone(),
+                current_tag: current_tag.clone(),
+                latest: newest.clone(),
+            });
+        }
+    }
+    check
+}
+
+/// The result of the last check.
+#[derive(Debug, Default)]
+pub struct UpdateState {
+    available: std::sync::Mutex<Vec<AvailableUpdate>>,
+}
+
+impl UpdateState {
+    pub fn available(&self) -> Vec<AvailableUpdate> {
+        self.available.lock().unwrap().clone()
+    }
+
+    /// Stores the updates. Returns whether they differ from the previous check.
+    fn replace(&self, updates: Vec<AvailableUpdate>) -> bool {
+        let mut available = self.available.lock().unwrap();
+        let changed = !available
+            .iter()
+            .map(|u| u.key())
+            .eq(updates.iter().map(|u| u.key()));
+        *available = updates;
+        changed
+    }
+
+    fn remove(&self, profile_id: &str) {
+        self.available
+            .lock()
+            .unwrap()
+            .retain(|u| u.profile_id != profile_id);
+    }
+}
+
+/// Runs a check and emits `UPDATES_AVAILABLE` if the available updates changed.
+fn run_check(app: &tauri::AppHandle) -> UpdateCheck {
+    use tauri::Manager;
+    let settings = app.state::<AppState>().get_settings().unwrap();
+    let check = check_updates(&settings);
+    let state = app.state::<UpdateState>();
+    if state.replace(check.updates.clone()) && !check.updates.is_empty() {
+        info!("{} update(s) available", check.updates.len());
+        app.emit(UPDATES_AVAILABLE, &check.updates)
+            .unwrap_or_else(|e| warn!("Failed to emit available updates: {}", e));
+    }
+    check
+}
+
+/// Checks for updates every `interval_minutes` on a background thread, starting now.
+/// The settings are re-read before each check.
+pub fn spawn_update_checks(app: tauri::AppHandle) {
+    use tauri::Manager;
+    std::thread::spawn(move || loop {
+        let settings = app.state::<AppState>().get_settings().unwrap().update_check;
+        let mut wait = std::time::Duration::from_secs(settings.interval_minutes.max(1) * 60);
+        if settings.enabled {
+            if let Some(until) = run_check(&app).rate_limited_until {
+                info!("Update check is rate-limited until {}", until);
+                wait = wait.max((until - Utc::now()).to_std().unwrap_or_default());
+            }
+        }
+        std::thread::sleep(wait);
+    });
+}
+
+pub mod commands {
+    use super::*;
+    use crate::cdda::session::SessionState;
+    use tauri::{AppHandle, Manager};
+
+    /// Updates found by the last check.
+    #[tauri::command]
+    pub fn list_available_updates(updates: tauri::State<'_, UpdateState>) -> Vec<AvailableUpdate> {
+        updates.available()
+    }
+
+    /// Checks for updates now. `UPDATES_AVAILABLE` is emitted as in background checks.
+    #[tauri::command]
+    pub async fn check_for_updates(app: AppHandle) -> Result<Vec<AvailableUpdate>, String> {
+        tauri::async_runtime::spawn_blocking(move || {
+            let check = run_check(&app);
+            match check.rate_limited_until {
+                Some(until) if check.updates.is_empty() => Err(RateLimited { until }.to_string()),
+                _ => Ok(check.updates),
+            }
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+
+    /// Installs the newest build of the profile's channel next to the current one,
+    /// and switches the profile to it. The previous build is kept.
+    /// A build already installed (e.g. by another profile following the channel) is reused.
+    /// Refused while the game of the profile is running.
+    #[tauri::command]
+    pub async fn update_profile_game(
+        app: AppHandle,
+        profile_id: String,
+    ) -> Result<crate::install::GameInstall, String> {
+        let sessions = app.state::<SessionState>();
+        sessions
+            .ensure_not_running(&profile_id)
+            .map_err(|e| e.to_string())?;
+        let settings = app.state::<AppState>().get_settings().unwrap();
+        let current_install = settings
+            .profiles
+            .iter()
+            .find(|p| p.get_id() == profile_id)
+            .and_then(|p| p.get_install_id())
+            .map(|id| id.to_string());
+        // The last check is outdated if the profile switched builds since.
+        let update = app
+            .state::<UpdateState>()
+            .available()
+            .into_iter()
+            .find(|u| {
+                u.profile_id == profile_id && Some(&u.install_id) == current_install.as_ref()
+            });
+        let update = match update {
+            Some(update) => update,
+            None => tauri::async_runtime::spawn_blocking(move || check_updates(&settings))
+                .await
+                .map_err(|e| e.to_string())?
+                .updates
+                .into_iter()
+                .find(|u| u.profile_id == profile_id)
+                .ok_or_else(|| format!("No update available for {}", profile_id))?,
+        };
+        let installed = app
+            .state::<AppState>()
+            .get_settings()
+            .unwrap()
+            .get_release_install(&update.latest.source, &update.latest.tag_name)
+            .cloned();
+        let install = match installed {
+            Some(install) => {
+                app.state::<AppState>().update_settings(|settings| {
+                    sessions.ensure_not_running(&profile_id)?;
+                    settings.set_profile_install(&profile_id, &install.id)
+                })?;
+                install
+            }
+            None => {
+                crate::install::pipeline::commands::install_release(
+                    app.clone(),
+                    update.latest,
+                    Some(profile_id.clone()),
+                )
+                .await?
+            }
+        };
+        app.state::<UpdateState>().remove(&profile_id);
+        Ok(install)
+    }
+
+    #[tauri::command]
+    pub fn set_profile_update_channel(
+        state: tauri::State<'_, AppState>,
+        profile_id: String,
+        channel: Option<ReleaseChannel>,
+    ) -> Result<(), String> {
+        state.update_settings(|settings| settings.set_update_channel(&profile_id, channel))
+    }
+
+    #[tauri::command]
+    pub fn set_update_check_settings(
+        state: tauri::State<'_, AppState>,
+        update_check: UpdateCheckSettings,
+    ) -> Result<(), String> {
+        state.update_settings(|settings| {
+            settings.set_update_check_settings(update_check);
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ReleaseChannel::*;
+
+    #[test]
+    fn offers_newer_builds_of_the_same_channel() {
+        let current = "cdda-experimental-2024-03-01-0613";
+        assert!(offers_update(
+            current,
+            Experimental,
+            Experimental,
+            "cdda-experimental-2024-03-02-0000"
+        ));
+        assert!(!offers_update(current, Experimental, Experimental, current));
+        assert!(!offers_update(
+            current,
+            Experimental,
+            Experimental,
+            "cdda-experimental-2024-01-01-0000"
+        ));
+        assert!(offers_update("0.G", Stable, Stable, "0.H"));
+        assert!(!offers_update("0.H-1", Stable, Stable, "0.H"));
+    }
+
+    #[test]
+    fn offers_the_newest_build_of_another_channel() {
+        // Experimental versions order below stable ones, whatever their date.
+        assert!(offers_update(
+            "0.H",
+            Stable,
+            Experimental,
+            "cdda-experimental-2024-03-01-0613"
+        ));
+        // 0.G is older than the experimental build, but the profile asked for stable builds.
+        assert!(offers_update(
+            "cdda-experimental-2024-03-01-0613",
+            Experimental,
+            Stable,
+            "0.G"
+        ));
+        assert!(offers_update("0.H", Local, Stable, "0.H-1"));
+        assert!(!offers_update("0.H", Local, Stable, "0.H"));
+    }
+}
@@ -1,33 +1,180 @@
 use crate::prelude::*;
+use std::sync::OnceLock;
 
-/// Returns the path to the application's data directory.
-/// On Windows, this is the directory containing the executable.
-/// On macOS, this is the `cataylzer` subdirectory of the user's configuration directory.
-pub fn get_app_data_dir() -> PathBuf {
-    #[cfg(target_os = "windows")]
-    {
-        let exe_path = std::env::current_exe().unwrap();
-        exe_path.parent().unwrap().to_path_buf()
+/// Environment variable that turns on portable mode. Its value is the portable root directory
+/// (relative paths are resolved against the executable's directory).
+pub const PORTABLE_ENV: &str = "CATALYZER_PORTABLE_DIR";
+/// Marker file placed next to the executable that turns on portable mode.
+/// If the file is not empty, its first line is used as the portable root directory
+/// (relative paths are resolved against the executable's directory).
+pub const PORTABLE_MARKER: &str = "portable.txt";
+
+/// Root directories used by the application.
+///
+/// - `config`: settings file (`setting.yaml`)
+/// - `data`: profiles, moddata and the CDDA clone
+/// - `cache`: logs and temporary files (`.cdda/tmp`)
+#[derive(Debug, Clone)]
+pub struct AppDirs {
+    pub config: PathBuf,
+    pub data: PathBuf,
+    pub cache: PathBuf,
+    pub portable: bool,
+}
+
+impl AppDirs {
+    fn single_root(root: PathBuf, portable: bool) -> Self {
+        Self {
+            config: root.clone(),
+            data: root.clone(),
+            cache: root,
+            portable,
+        }
+    }
+
+    /// Resolves the directories. Portable mode takes precedence over the platform defaults.
+    pub fn resolve() -> Self {
+        match portable_root() {
+            Some(root) => Self::single_root(root, true),
+            None => Self::platform_default(),
+        }
+    }
+
+    /// Platform defaults:
+    /// - Windows: everything is stored in the directory containing the executable.
+    /// - macOS: everything is stored in the `cataylzer` subdirectory of the user's configuration directory.
+    /// - Linux: directories follow the XDG Base Directory Specification.
+    fn platform_default() -> Self {
+        #[cfg(target_os = "windows")]
+        {
+            Self::single_root(exe_dir(), false)
+        }
+        #[cfg(target_os = "macos")]
+        {
+            Self::single_root(dirs::config_dir().unwrap().join("cataylzer"), false)
+        }
+        #[cfg(target_os = "linux")]
+        {
+            let home = dirs::home_dir().unwrap();
+            Self {
+                config: xdg_dir("XDG_CONFIG_HOME", home.join(".config")).join(APP_DIR_NAME),
+                data: xdg_dir("XDG_DATA_HOME", home.join(".local").join("share"))
+                    .join(APP_DIR_NAME),
+                cache: xdg_dir("XDG_CACHE_HOME", home.join(".cache")).join(APP_DIR_NAME),
+                portable: false,
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+const APP_DIR_NAME: &str = "catalyzer";
+
+/// Returns `$var` if it is set to an absolute path, otherwise `fallback`.
+/// (Relative paths are invalid per the XDG spec and must be ignored.)
+#[cfg(target_os = "linux")]
+fn xdg_dir(var: &str, fallback: PathBuf) -> PathBuf {
+    std::env::var_os(var)
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .unwrap_or(fallback)
+}
+
+fn exe_dir() -> PathBuf {
+    let exe_path = std::env::current_exe().unwrap();
+    exe_path.parent().unwrap().to_path_buf()
+}
+
+/// Returns the portable root if portable mode is enabled by the env var or the marker file.
+fn portable_root() -> Option<PathBuf> {
+    let exe_dir = exe_dir();
+    if let Some(root) = std::env::var_os(PORTABLE_ENV).filter(|v| !v.is_empty()) {
+        return Some(exe_dir.join(root));
+    }
+    let marker = exe_dir.join(PORTABLE_MARKER);
+    if !marker.is_file() {
+        return None;
     }
-    #[cfg(target_os = "macos")]
-    {
-        dirs::config_dir().unwrap().join("cataylzer")
+    let content = std::fs::read_to_string(&marker).unwrap_or_default();
+    match content.lines().next().map(str::trim) {
+        Some(line) if !line.is_empty() => Some(exe_dir.join(line)),
+        _ => Some(exe_dir),
     }
 }
 
+/// Returns the resolved application directories. They are resolved only once per process.
+pub fn app_dirs() -> &'static AppDirs {
+    static APP_DIRS: OnceLock<AppDirs> = OnceLock::new();
+    APP_DIRS.get_or_init(|| {
+        let resolved = AppDirs::resolve();
+        debug!("Resolved app dirs: {:?}", resolved);
+        resolved
+    })
+}
+
+pub fn config_dir() -> PathBuf {
+    app_dirs().config.clone()
+}
+
+pub fn data_dir() -> PathBuf {
+    app_dirs().data.clone()
+}
+
+pub fn cache_dir() -> PathBuf {
+    app_dirs().cache.clone()
+}
+
 pub fn log_dir() -> PathBuf {
-    get_app_data_dir().join("log")
+    cache_dir().join("log")
+}
+
+pub fn tmp_dir() -> PathBuf {
+    cache_dir().join(".cdda").join("tmp")
 }
 
 pub fn cdda_clone_dir() -> PathBuf {
-    let app_data_dir = get_app_data_dir();
-    app_data_dir.join(".cdda").join("Cataclysm-DDA")
+    data_dir().join(".cdda").join("Cataclysm-DDA")
 }
 
 pub fn profile_dir(name_with_id: &str) -> PathBuf {
-    get_app_data_dir().join("profiles").join(name_with_id)
+    data_dir().join("profiles").join(name_with_id)
 }
 
 pub fn moddata_dir() -> PathBuf {
-    get_app_data_dir().join("moddata")
+    data_dir().join("moddata")
+}
+
+pub fn session_dir() -> PathBuf {
+    data_dir().join("sessions")
+}
+
+/// Versioned game installs, one directory per release source and tag.
+pub fn installs_dir() -> PathBuf {
+    data_dir().join("installs")
+}
+
+/// Downloaded archives, stored by SHA-256 digest (see `install::download`).
+pub fn download_cache_dir() -> PathBuf {
+    cache_dir().join("downloads")
+}
+
+/// Save backups taken when a profile switches builds, one directory per profile.
+pub fn save_backup_dir() -> PathBuf {
+    data_dir().join("save_backups")
+}
+
+/// Points the app directories of the test process to a temporary directory.
+/// Call before anything else resolves them.
+#[cfg(test)]
+pub fn use_temp_dirs() {
+    static DIR: OnceLock<tempfile::TempDir> = OnceLock::new();
+    DIR.get_or_init(|| {
+        let dir = tempfile::tempdir().unwrap();
+        std::env::set_var(PORTABLE_ENV, dir.path());
+        dir
+    });
+    assert!(
+        app_dirs().portable,
+        "App dirs were resolved before the tests"
+    );
 }
@@ -1,9 +1,20 @@
+use crate::cdda::hooks::ProfileHooks;
+use crate::cdda::release::asset::AssetPreference;
+use crate::cdda::release::github;
+use crate::cdda::release::source::{default_release_sources, ReleaseSource, DEFAULT_SOURCE_ID};
 use crate::files::{get_modinfo_path, remove_dir_all, symlink::list_symlinks};
+use crate::git::sparse::SparseCheckout;
 use crate::git::{open, try_checkout_to};
+use crate::install::build::BuildSettings;
+use crate::install::retention::RetentionPolicy;
+use crate::install::rollback::{self, PreviousBuild};
+use crate::install::update::UpdateCheckSettings;
+use crate::install::{GameInstall, ReleaseChannel};
 use crate::model::{LocalVersion, Mod, ModInfo};
 use crate::paths;
 use crate::prelude::*;
 use chrono::{DateTime, Utc};
+use std::collections::BTreeMap;
 use std::fs;
 use std::io::Write;
 use std::sync::Mutex;
@@ -34,6 +45,40 @@ impl UserDataPaths {
             gfx: root.join("gfx"),
         }
     }
+
+    /// Paths exposed to hooks as environment variables.
+    pub fn env_vars(&self) -> Vec<(&'static str, &PathBuf)> {
+        vec![
+            ("CATALYZER_PROFILE_ROOT", &self.root),
+            ("CATALYZER_MODS_DIR", &self.mods),
+            ("CATALYZER_CONFIG_DIR", &self.config),
+            ("CATALYZER_FONT_DIR", &self.font),
+            ("CATALYZER_SAVE_DIR", &self.save),
+            ("CATALYZER_SOUND_DIR", &self.sound),
+            ("CATALYZER_GFX_DIR", &self.gfx),
+        ]
+    }
+
+    pub fn save_dir(&self) -> &Path {
+        &self.save
+    }
+}
+
+/// Extra options applied when the game is launched from a profile.
+///
+/// `args`, the values of `envs` and `working_dir` may contain the following placeholders,
+/// which are expanded at launch:
+/// `{profile_root}`, `{profile_id}`, `{profile_name}`, `{game_path}`, `{game_dir}`
+#[derive(Debug, Default, Deserialize, Serialize, Clone)]
+pub struct LaunchOptions {
+    /// Extra command line arguments. e.g. `["--world", "MyWorld"]`
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub envs: BTreeMap<String, String>,
+    /// Overrides the working directory (defaults to the directory containing the game binary).
+    #[serde(default)]
+    pub working_dir: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -44,6 +89,22 @@ pub struct Profile {
     profile_path: UserDataPaths,
     mod_status: Vec<Mod>,
     is_active: bool,
+    #[serde(default)]
+    launch_options: LaunchOptions,
+    #[serde(default)]
+    hooks: ProfileHooks,
+    /// The `GameInstall` this profile plays. `game_path` follows the install.
+    #[serde(default)]
+    install_id: Option<String>,
+    /// Channel checked for updates. Defaults to the channel of the install.
+    #[serde(default)]
+    update_channel: Option<ReleaseChannel>,
+    /// The build played before the last switch, for rollback.
+    #[serde(default)]
+    previous_build: Option<PreviousBuild>,
+    /// Roll back if a new build crashes on its first launch.
+    #[serde(default)]
+    auto_rollback: bool,
 }
 
 impl Profile {
@@ -57,6 +118,12 @@ impl Profile {
             profile_path: UserDataPaths::new(profile_dir),
             mod_status: Vec::new(),
             is_active: false,
+            launch_options: LaunchOptions::default(),
+            hooks: ProfileHooks::default(),
+            install_id: None,
+            update_channel: None,
+            previous_build: None,
+            auto_rollback: false,
         }
     }
 
@@ -92,6 +159,22 @@ impl Profile {
             .collect()
     }
 
+    pub fn get_id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.is_active
+    }
+
+    pub fn get_mods(&self) -> &[Mod] {
+        &self.mod_status
+    }
+
     pub fn get_game_path(&self) -> Option<PathBuf> {
         self.game_path.clone()
     }
@@ -99,6 +182,49 @@ impl Profile {
     pub fn get_profile_root_dir(&self) -> PathBuf {
         self.profile_path.root.clone()
     }
+
+    pub fn get_config_dir(&self) -> PathBuf {
+        self.profile_path.config.clone()
+    }
+
+    pub fn get_launch_options(&self) -> &LaunchOptions {
+        &self.launch_options
+    }
+
+    pub fn get_install_id(&self) -> Option<&str> {
+        self.install_id.as_deref()
+    }
+
+    pub fn get_update_channel(&self) -> Option<ReleaseChannel> {
+        self.update_channel
+    }
+
+    pub fn get_previous_build(&self) -> Option<&PreviousBuild> {
+        self.previous_build.as_ref()
+    }
+
+    pub fn get_auto_rollback(&self) -> bool {
+        self.auto_rollback
+    }
+
+    pub fn get_hooks(&self) -> &ProfileHooks {
+        &self.hooks
+    }
+
+    pub fn get_user_data_paths(&self) -> &UserDataPaths {
+        &self.profile_path
+    }
+}
+
+/// Validates a user supplied game path.
+/// Returns `None` if the path does not point to a game executable (or app bundle).
+pub fn parse_game_path(path: &str) -> Option<PathBuf> {
+    let path = path.trim().trim_matches('"');
+    let game_path = PathBuf::from(path);
+    match game_path.file_name().and_then(std::ffi::OsStr::to_str) {
+        Some("cataclysm-tiles.exe" | "Cataclysm.app" | "cataclysm-tiles") => Some(game_path),
+        _ => None,
+    }
 }
 
 impl Default for Profile {
@@ -115,6 +241,30 @@ pub struct Settings {
     pub language: String,
     pub mod_data_path: PathBuf,
     pub profiles: Vec<Profile>,
+    /// Game builds installed on this machine.
+    #[serde(default)]
+    pub installs: Vec<GameInstall>,
+    /// Where releases are listed and downloaded from.
+    #[serde(default = "default_release_sources")]
+    pub release_sources: Vec<ReleaseSource>,
+    /// Personal access token for the GitHub API. `GITHUB_TOKEN` is used if unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub github_token: Option<String>,
+    /// Which asset of a release to download by default.
+    #[serde(default)]
+    pub asset_preference: AssetPreference,
+    /// Background checks for newer game builds.
+    #[serde(default)]
+    pub update_check: UpdateCheckSettings,
+    /// Which old game builds the cleanup keeps.
+    #[serde(default)]
+    pub retention: RetentionPolicy,
+    /// Building the game from source checkouts.
+    #[serde(default)]
+    pub build: BuildSettings,
+    /// Paths checked out in the CDDA clone.
+    #[serde(default)]
+    pub cdda_sparse_checkout: SparseCheckout,
 }
 
 impl Default for Settings {
@@ -123,6 +273,14 @@ impl Default for Settings {
             language: "ja".into(),
             mod_data_path: paths::moddata_dir(),
             profiles: vec![Profile::default()],
+            installs: Vec::new(),
+            release_sources: default_release_sources(),
+            github_token: None,
+            asset_preference: AssetPreference::default(),
+            update_check: UpdateCheckSettings::default(),
+            retention: RetentionPolicy::default(),
+            build: BuildSettings::default(),
+            cdda_sparse_checkout: SparseCheckout::default(),
         }
     }
 }
@@ -173,14 +331,76 @@ impl Settings {
     }
 
     pub fn new() -> Self {
-        let config_file = paths::get_app_data_dir().join(SETTINGS_FILENAME);
-        if !config_file.exists() {
+        let config_file = paths::config_dir().join(SETTINGS_FILENAME);
+        let settings = if !config_file.exists() {
             let mut settings = Self::default();
             settings.post_init();
             settings
         } else {
-            let mut settings = Self::default();
-            settings.read_file()
+            let mut settings = Self::default().read_file();
+            settings.adopt_untracked_installs();
+            settings.backfill_cdda_versions();
+            settings
+        };
+        github::set_token(settings.github_token.clone());
+        settings
+    }
+
+    /// Parses the versions of builds registered before versions were parsed.
+    fn backfill_cdda_versions(&mut self) {
+        let mut changed = false;
+        for install in self
+            .installs
+            .iter_mut()
+            .filter(|i| i.cdda_version.is_none())
+        {
+            install.cdda_version = install
+                .tag
+                .as_deref()
+                .or(install.version.as_deref())
+                .and_then(|v| v.parse().ok());
+            changed |= install.cdda_version.is_some();
+        }
+        if changed {
+            self.write_file();
+        }
+    }
+
+    /// Registers the game paths of profiles created before the install registry existed
+    /// as local installs, so that every profile with a game references an install.
+    fn adopt_untracked_installs(&mut self) {
+        let mut changed = false;
+        for index in 0..self.profiles.len() {
+            let profile = &self.profiles[index];
+            if profile.install_id.is_some() {
+                continue;
+            }
+            let Some(game_path) = profile.game_path.clone() else {
+                continue;
+            };
+            let install = match self.installs.iter().find(|i| i.game_path == game_path) {
+                Some(install) => install.clone(),
+                None => {
+                    let Some(install_dir) = game_path.parent() else {
+                        continue;
+                    };
+                    match GameInstall::detect(install_dir, None, ReleaseChannel::Local) {
+                        Ok(install) => {
+                            self.installs.push(install.clone());
+                            install
+                        }
+                        Err(e) => {
+                            debug!("Skipped registering {:?}: {}", game_path, e);
+                            continue;
+                        }
+                    }
+                }
+            };
+            self.profiles[index].install_id = Some(install.id);
+            changed = true;
+        }
+        if changed {
+            self.write_file();
         }
     }
 
@@ -197,9 +417,21 @@ impl Settings {
     }
 
     pub fn remove_profile(&mut self, profile_id: String) {
+        if self.get_active_profile().id == profile_id {
+            // switch to default profile
+            self.set_active_profile("default".to_string()).unwrap();
+        }
         if let Some(index) = self.profiles.iter().position(|x| x.id == profile_id) {
             let profile = &self.profiles[index];
             self.remove_profile_dir(profile);
+            crate::cdda::session::remove_sessions(&profile.id);
+            if let Some(backup) = profile
+                .previous_build
+                .as_ref()
+                .and_then(|b| b.save_backup.as_ref())
+            {
+                rollback::remove_backup(backup);
+            }
             self.profiles.remove(index); // 削除はindex使うので最後
             self.write_file();
         }
@@ -220,6 +452,275 @@ impl Settings {
         Ok(())
     }
 
+    pub fn set_launch_options(&mut self, profile_id: &str, options: LaunchOptions) -> Result<()> {
+        let profile = self
+            .profiles
+            .iter_mut()
+            .find(|p| p.id == profile_id)
+            .ok_or_else(|| anyhow!("Profile not found: {}", profile_id))?;
+        profile.launch_options = options;
+        self.write_file();
+        Ok(())
+    }
+
+    pub fn set_hooks(&mut self, profile_id: &str, hooks: ProfileHooks) -> Result<()> {
+        let profile = self
+            .profiles
+            .iter_mut()
+            .find(|p| p.id == profile_id)
+            .ok_or_else(|| anyhow!("Profile not found: {}", profile_id))?;
+        profile.hooks = hooks;
+        self.write_file();
+        Ok(())
+    }
+
+    pub fn set_update_channel(
+        &mut self,
+        profile_id: &str,
+        channel: Option<ReleaseChannel>,
+    ) -> Result<()> {
+        let profile = self
+            .profiles
+            .iter_mut()
+            .find(|p| p.id == profile_id)
+            .ok_or_else(|| anyhow!("Profile not found: {}", profile_id))?;
+        profile.update_channel = channel;
+        self.write_file();
+        Ok(())
+    }
+
+    pub fn set_update_check_settings(&mut self, update_check: UpdateCheckSettings) {
+        self.update_check = update_check;
+        self.write_file();
+    }
+
+    pub fn set_retention_policy(&mut self, policy: RetentionPolicy) {
+        self.retention = policy;
+        self.write_file();
+    }
+
+    pub fn set_build_settings(&mut self, build: BuildSettings) {
+        self.build = build;
+        self.write_file();
+    }
+
+    pub fn set_cdda_sparse_checkout(&mut self, sparse: SparseCheckout) {
+        self.cdda_sparse_checkout = sparse;
+        self.write_file();
+    }
+
+    /// Adds an install to the registry. An existing entry for the same directory is replaced,
+    /// and the profiles using it are moved to the new entry.
+    pub fn register_install(&mut self, install: GameInstall) {
+        if let Some(old) = self
+            .installs
+            .iter()
+            .position(|i| i.install_dir == install.install_dir)
+        {
+            let old = self.installs.remove(old);
+            self.profiles
+                .iter_mut()
+                .filter(|p| p.install_id.as_deref() == Some(old.id.as_str()))
+                .for_each(|p| {
+                    p.install_id = Some(install.id.clone());
+                    p.game_path = Some(install.game_path.clone());
+                });
+        }
+        self.installs.push(install);
+        self.write_file();
+    }
+
+    /// Removes an install from the registry. Fails if a profile still uses it.
+    pub fn unregister_install(&mut self, install_id: &str) -> Result<()> {
+        let used_by = self.profiles_using_install(install_id);
+        ensure!(
+            used_by.is_empty(),
+            "The install is used by profiles: {}",
+            used_by.join(", ")
+        );
+        let index = self
+            .installs
+            .iter()
+            .position(|i| i.id == install_id)
+            .ok_or_else(|| anyhow!("Install not found: {}", install_id))?;
+        self.installs.remove(index);
+        self.write_file();
+        Ok(())
+    }
+
+    pub fn set_install_pinned(&mut self, install_id: &str, pinned: bool) -> Result<()> {
+        let install = self
+            .installs
+            .iter_mut()
+            .find(|i| i.id == install_id)
+            .ok_or_else(|| anyhow!("Install not found: {}", install_id))?;
+        install.pinned = pinned;
+        self.write_file();
+        Ok(())
+    }
+
+    pub fn get_install(&self, install_id: &str) -> Option<&GameInstall> {
+        self.installs.iter().find(|i| i.id == install_id)
+    }
+
+    /// The install of a release, if it is installed.
+    pub fn get_release_install(&self, source: &str, tag: &str) -> Option<&GameInstall> {
+        self.installs
+            .iter()
+            .find(|i| i.source.as_deref() == Some(source) && i.tag.as_deref() == Some(tag))
+    }
+
+    /// Ids of the profiles referencing the install.
+    pub fn profiles_using_install(&self, install_id: &str) -> Vec<String> {
+        self.profiles
+            .iter()
+            .filter(|p| p.install_id.as_deref() == Some(install_id))
+            .map(|p| p.id.clone())
+            .collect()
+    }
+
+    /// Switches the game build played by a profile.
+    /// The previous build is remembered and the saves are backed up, for rollback.
+    pub fn set_profile_install(&mut self, profile_id: &str, install_id: &str) -> Result<()> {
+        let game_path = self
+            .get_install(install_id)
+            .ok_or_else(|| anyhow!("Install not found: {}", install_id))?
+            .game_path
+            .clone();
+        let profile = self
+            .profiles
+            .iter_mut()
+            .find(|p| p.id == profile_id)
+            .ok_or_else(|| anyhow!("Profile not found: {}", profile_id))?;
+        if profile.game_path.is_some() && profile.install_id.as_deref() != Some(install_id) {
+            let save_backup = rollback::backup_saves(profile)
+                .map_err(|e| warn!("{:#}", e))
+                .ok()
+                .flatten();
+            // Only the latest switch can be rolled back.
+            let previous = profile.previous_build.replace(PreviousBuild {
+                install_id: profile.install_id.clone(),
+                game_path: profile.game_path.clone(),
+                switched_at: chrono::Local::now(),
+                save_backup,
+                launched: false,
+            });
+            if let Some(backup) = previous.and_then(|b| b.save_backup) {
+                rollback::remove_backup(&backup);
+            }
+        }
+        profile.install_id = Some(install_id.to_string());
+        profile.game_path = Some(game_path);
+        self.write_file();
+        Ok(())
+    }
+
+    /// Swaps the current build of a profile with the previous one.
+    /// `save_backup` becomes the backup of the build switched away from.
+    pub fn swap_previous_build(
+        &mut self,
+        profile_id: &str,
+        save_backup: Option<PathBuf>,
+    ) -> Result<()> {
+        let profile = self
+            .profiles
+            .iter_mut()
+            .find(|p| p.id == profile_id)
+            .ok_or_else(|| anyhow!("Profile not found: {}", profile_id))?;
+        let previous = profile
+            .previous_build
+            .take()
+            .ok_or_else(|| anyhow!("{} has no previous build", profile.name))?;
+        if let Some(backup) = &previous.save_backup {
+            rollback::remove_backup(backup);
+        }
+        profile.previous_build = Some(PreviousBuild {
+            install_id: std::mem::replace(&mut profile.install_id, previous.install_id),
+            game_path: std::mem::replace(&mut profile.game_path, previous.game_path),
+            switched_at: chrono::Local::now(),
+            save_backup,
+            // The build rolled back to has been played before.
+            launched: true,
+        });
+        self.write_file();
+        Ok(())
+    }
+
+    /// Records the first launch since the last switch.
+    pub fn mark_build_launched(&mut self, profile_id: &str) -> Result<()> {
+        let previous = self
+            .profiles
+            .iter_mut()
+            .find(|p| p.id == profile_id)
+            .and_then(|p| p.previous_build.as_mut())
+            .ok_or_else(|| anyhow!("No previous build for {}", profile_id))?;
+        previous.launched = true;
+        self.write_file();
+        Ok(())
+    }
+
+    pub fn set_auto_rollback(&mut self, profile_id: &str, enabled: bool) -> Result<()> {
+        let profile = self
+            .profiles
+            .iter_mut()
+            .find(|p| p.id == profile_id)
+            .ok_or_else(|| anyhow!("Profile not found: {}", profile_id))?;
+        profile.auto_rollback = enabled;
+        self.write_file();
+        Ok(())
+    }
+
+    /// Ids of the profiles that can roll back to the install.
+    pub fn profiles_with_previous_install(&self, install_id: &str) -> Vec<String> {
+        self.profiles
+            .iter()
+            .filter(|p| {
+                p.previous_build
+                    .as_ref()
+                    .and_then(|b| b.install_id.as_deref())
+                    == Some(install_id)
+            })
+            .map(|p| p.id.clone())
+            .collect()
+    }
+
+    /// Finds a release source by id. `None` selects the default (CDDA) source.
+    pub fn release_source(&self, source_id: Option<&str>) -> Result<ReleaseSource> {
+        let source_id = source_id.unwrap_or(DEFAULT_SOURCE_ID);
+        self.release_sources
+            .iter()
+            .find(|s| s.id == source_id)
+            .cloned()
+            .ok_or_else(|| anyhow!("Release source not found: {}", source_id))
+    }
+
+    pub fn set_asset_preference(&mut self, preference: AssetPreference) {
+        self.asset_preference = preference;
+        self.write_file();
+    }
+
+    pub fn set_github_token(&mut self, token: Option<String>) {
+        self.github_token = token.filter(|t| !t.trim().is_empty());
+        github::set_token(self.github_token.clone());
+        self.write_file();
+    }
+
+    pub fn set_release_sources(&mut self, sources: Vec<ReleaseSource>) -> Result<()> {
+        for source in &sources {
+            source.validate()?;
+        }
+        let mut ids = sources.iter().map(|s| s.id.as_str()).collect::<Vec<_>>();
+        ids.sort_unstable();
+        ids.dedup();
+        ensure!(
+            ids.len() == sources.len(),
+            "Release source ids must be unique"
+        );
+        self.release_sources = sources;
+        self.write_file();
+        Ok(())
+    }
+
     pub fn get_active_profile(&self) -> Profile {
         self.profiles
             .iter()
@@ -308,19 +809,22 @@ trait Config {
 }
 
 impl Config for Settings {
+    /// Writes to a temporary file renamed into place, so that the GUI and the CLI never read a
+    /// half-written file.
     fn write_file(&self) {
-        let config_root = paths::get_app_data_dir();
+        let config_root = paths::config_dir();
         if !config_root.exists() {
             fs::create_dir_all(&config_root).unwrap();
         }
         let config_file = config_root.join(SETTINGS_FILENAME);
         let serialized = serde_yaml::to_string(self).unwrap();
-        let mut file = fs::File::create(config_file).unwrap();
+        let mut file = tempfile::NamedTempFile::new_in(&config_root).unwrap();
         file.write_all(serialized.as_bytes()).unwrap();
+        file.persist(config_file).unwrap();
     }
 
     fn read_file(&mut self) -> Settings {
-        let config_file = paths::get_app_data_dir().join(SETTINGS_FILENAME);
+        let config_file = paths::config_dir().join(SETTINGS_FILENAME);
         let input = fs::read_to_string(config_file).unwrap();
         serde_yaml::from_str(&input).unwrap_or_else(|_| {
             debug!("Error: Failed to read config file");
@@ -329,42 +833,87 @@ impl Config for Settings {
     }
 }
 
+/// Modification time and size of the settings file.
+type FileStamp = (std::time::SystemTime, u64);
+
+fn settings_file_stamp() -> Option<FileStamp> {
+    let metadata = fs::metadata(paths::config_dir().join(SETTINGS_FILENAME)).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// The settings of the running app.
+///
+/// catalyzer-cli writes the settings file directly. The settings are reloaded whenever the file
+/// changed since it was last seen, so that the app does not write back a stale copy over the
+/// changes of the CLI.
 #[derive(Debug)]
 pub struct AppState {
     settings: Mutex<Settings>,
+    seen: Mutex<Option<FileStamp>>,
 }
 
 impl AppState {
     pub fn new() -> Self {
+        let settings = Settings::new();
         Self {
-            settings: Mutex::new(Settings::new()),
+            settings: Mutex::new(settings),
+            seen: Mutex::new(settings_file_stamp()),
         }
     }
 
-    pub fn refresh_and_save_mod_status(&self) -> Result<Vec<Mod>> {
+    /// Locks the settings, reloading them first if the file was changed by another process.
+    fn lock(&self) -> std::sync::MutexGuard<'_, Settings> {
         let mut settings = self.settings.lock().unwrap();
+        let mut seen = self.seen.lock().unwrap();
+        let stamp = settings_file_stamp();
+        if stamp.is_some() && stamp != *seen {
+            // Also reloads after our own writes, which reads back the same settings.
+            debug!("Settings file changed. Reloading");
+            *settings = settings.read_file();
+            github::set_token(settings.github_token.clone());
+            *seen = stamp;
+        }
+        settings
+    }
+
+    pub fn refresh_and_save_mod_status(&self) -> Result<Vec<Mod>> {
+        let mut settings = self.lock();
         let profile = settings.get_active_profile();
         settings.mutate_state_mod_status(&profile)
     }
 
     pub fn get_settings(&self) -> Option<Settings> {
-        let settings = self.settings.lock().unwrap();
+        let settings = self.lock();
         Some(settings.clone())
     }
 
     pub fn get_game_mod_dir(&self) -> PathBuf {
-        let settings = self.settings.lock().unwrap();
+        let settings = self.lock();
         settings.get_game_mod_dir()
     }
+
+    /// Runs `f` on the settings while holding the lock.
+    pub fn update_settings<T>(
+        &self,
+        f: impl FnOnce(&mut Settings) -> Result<T>,
+    ) -> Result<T, String> {
+        let mut settings = self.lock();
+        f(&mut settings).map_err(|e| e.to_string())
+    }
 }
 
 pub mod commands {
     use super::*;
+    use crate::cdda::session::SessionState;
 
     #[tauri::command]
     pub fn get_settings(state: tauri::State<'_, AppState>) -> Result<Settings, String> {
-        let settings = state.settings.lock().unwrap();
-        Ok(settings.clone())
+        let mut settings = state.lock().clone();
+        // The token is write-only from the frontend.
+        if settings.github_token.is_some() {
+            settings.github_token = Some("********".to_string());
+        }
+        Ok(settings)
     }
 
     #[tauri::command]
@@ -374,16 +923,9 @@ pub mod commands {
         name: String,
         game_path: Option<String>,
     ) -> Result<Profile, String> {
-        let mut settings = state.settings.lock().unwrap();
-
-        let game_path = game_path.and_then(|path| {
-            let path = path.trim().trim_matches('"');
-            let game_path = PathBuf::from(path);
-            match game_path.file_name().and_then(std::ffi::OsStr::to_str) {
-                Some("cataclysm-tiles.exe" | "Cataclysm.app") => Some(game_path),
-                _ => None,
-            }
-        });
+        let mut settings = state.lock();
+
+        let game_path = game_path.and_then(|path| parse_game_path(&path));
 
         if game_path.is_none() && game_path.is_some() {
             return Err("Invalid game path".to_string());
@@ -400,12 +942,7 @@ pub mod commands {
         state: tauri::State<'_, AppState>,
         profile_id: String,
     ) -> Result<(), String> {
-        let mut settings = state.settings.lock().unwrap();
-        let current = settings.get_active_profile();
-        if current.id == profile_id {
-            // switch to default profile
-            settings.set_active_profile("default".to_string()).unwrap();
-        }
+        let mut settings = state.lock();
         settings.remove_profile(profile_id);
         Ok(())
     }
@@ -413,9 +950,13 @@ pub mod commands {
     #[tauri::command]
     pub fn set_profile_active(
         state: tauri::State<'_, AppState>,
+        sessions: tauri::State<'_, SessionState>,
         profile_id: String,
     ) -> Result<(), String> {
-        let mut settings = state.settings.lock().unwrap();
+        if sessions.is_any_active() {
+            return Err("Cannot switch profiles while the game is running".to_string());
+        }
+        let mut settings = state.lock();
         settings.set_active_profile(profile_id).map_err(|e| {
             debug!("Error: Failed to set active profile: {:?}", state);
             e.to_string()
@@ -424,7 +965,7 @@ pub mod commands {
 
     #[tauri::command]
     pub fn get_active_profile(state: tauri::State<'_, AppState>) -> Result<Profile, String> {
-        let settings = state.settings.lock().unwrap();
+        let settings = state.lock();
         let res = settings.get_active_profile();
         Ok(res)
     }
@@ -436,7 +977,7 @@ pub mod commands {
         name: String,
         game_path: Option<String>,
     ) -> Result<(), String> {
-        let mut settings = state.settings.lock().unwrap();
+        let mut settings = state.lock();
         let index = settings
             .profiles
             .iter()
@@ -450,14 +991,61 @@ pub mod commands {
 
             settings.profiles[index].name = name;
         }
-        settings.profiles[index].game_path = game_path.map(PathBuf::from);
+        let game_path = game_path.map(PathBuf::from);
+        if settings.profiles[index].game_path != game_path {
+            // A path set by hand keeps the install reference only if it points into a registered install.
+            let install_id = game_path.as_ref().and_then(|path| {
+                settings
+                    .installs
+                    .iter()
+                    .find(|i| &i.game_path == path)
+                    .map(|i| i.id.clone())
+            });
+            settings.profiles[index].install_id = install_id;
+        }
+        settings.profiles[index].game_path = game_path;
         settings.write_file();
         Ok(())
     }
 
+    #[tauri::command]
+    pub fn set_profile_launch_options(
+        state: tauri::State<'_, AppState>,
+        profile_id: String,
+        options: LaunchOptions,
+    ) -> Result<(), String> {
+        let mut settings = state.lock();
+        settings
+            .set_launch_options(&profile_id, options)
+            .map_err(|e| e.to_string())
+    }
+
+    #[tauri::command]
+    pub fn set_profile_hooks(
+        state: tauri::State<'_, AppState>,
+        profile_id: String,
+        hooks: ProfileHooks,
+    ) -> Result<(), String> {
+        let mut settings = state.lock();
+        settings
+            .set_hooks(&profile_id, hooks)
+            .map_err(|e| e.to_string())
+    }
+
+    /// Sets (or clears, with `None`) the GitHub personal access token.
+    #[tauri::command]
+    pub fn set_github_token(
+        state: tauri::State<'_, AppState>,
+        token: Option<String>,
+    ) -> Result<(), String> {
+        let mut settings = state.lock();
+        settings.set_github_token(token);
+        Ok(())
+    }
+
     #[tauri::command]
     pub fn get_current_profile(state: tauri::State<'_, AppState>) -> Result<Profile, String> {
-        let settings = state.settings.lock().unwrap();
+        let settings = state.lock();
         let res = settings.get_active_profile();
         Ok(res)
     }
@@ -467,7 +1055,7 @@ pub mod commands {
         state: tauri::State<'_, AppState>,
         lang: String,
     ) -> Result<(), String> {
-        let mut settings = state.settings.lock().unwrap();
+        let mut settings = state.lock();
         settings.set_language(&lang);
         settings.write_file();
         Ok(())
@@ -0,0 +1,240 @@
+//! Extraction of `.tar.gz` / `.tar.xz` archives.
+use crate::dmg::Progress;
+use crate::prelude::*;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+const TARBALL_EXTENSIONS: [(&str, Compression); 4] = [
+    (".tar.gz", Compression::Gzip),
+    (".tgz", Compression::Gzip),
+    (".tar.xz", Compression::Xz),
+    (".txz", Compression::Xz),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Xz,
+}
+
+impl Compression {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_string_lossy().to_lowercase();
+        TARBALL_EXTENSIONS
+            .iter()
+            .find(|(ext, _)| name.ends_with(ext))
+            .map(|(_, compression)| *compression)
+    }
+}
+
+pub fn is_tarball(path: &Path) -> bool {
+    Compression::from_path(path).is_some()
+}
+
+/// Counts the compressed bytes read, to report progress against the archive size.
+struct ProgressReader<R, F> {
+    inner: R,
+    read: u64,
+    total: u64,
+    last_emit: std::time::Instant,
+    on_progress: F,
+}
+
+impl<R: Read, F: FnMut(Progress)> Read for ProgressReader<R, F> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.read += n as u64;
+        if n == 0 || self.last_emit.elapsed() > std::time::Duration::from_millis(1000) {
+            (self.on_progress)(Progress {
+                progress: self.read,
+                total: self.total,
+            });
+            self.last_emit = std::time::Instant::now();
+        }
+        Ok(n)
+    }
+}
+
+/// Extracts a tarball into `target_dir`.
+///
+/// Like `zip::unzip`, entries escaping `target_dir` (absolute paths, `..`) are skipped,
+/// a single top-level directory is stripped, and existing files in `target_dir` are overwritten.
+/// Setting `cancel` stops the extraction before the next entry, leaving `target_dir` untouched.
+pub fn untar(
+    file_path: &Path,
+    target_dir: &Path,
+    cancel: &AtomicBool,
+    on_progress: impl FnMut(Progress),
+) -> Result<PathBuf> {
+    let compression = Compression::from_path(file_path)
+        .ok_or_else(|| anyhow!("Not a tarball: {}", file_path.display()))?;
+    let file = File::open(file_path)?;
+    let reader = ProgressReader {
+        total: file.metadata()?.len(),
+        inner: BufReader::new(file),
+        read: 0,
+        last_emit: std::time::Instant::now(),
+        on_progress,
+    };
+    let decoder: Box<dyn Read + '_> = match compression {
+        Compression::Gzip => Box::new(flate2::read::GzDecoder::new(reader)),
+        Compression::Xz => Box::new(xz2::read::XzDecoder::new(reader)),
+    };
+
+    std::fs::create_dir_all(target_dir)?;
+    // Extracted aside first, so that only the entries of the archive decide what is stripped.
+    let staging = tempfile::Builder::new()
+        .prefix(".untar")
+        .tempdir_in(target_dir)?;
+
+    let mut archive = tar::Archive::new(decoder);
+    archive.set_preserve_permissions(true);
+    for entry in archive.entries()? {
+        ensure!(!cancel.load(Ordering::SeqCst), "Extraction cancelled");
+        let mut entry = entry?;
+        let path = entry.path()?.into_owned();
+        // unpack_in refuses paths outside of the staging directory.
+        if !entry
+            .unpack_in(staging.path())
+            .with_context(|| format!("Failed to extract {}", path.display()))?
+        {
+            warn!("Skipped unsafe entry: {}", path.display());
+        }
+    }
+    strip_toplevel(staging.path())?;
+    move_into(staging.path(), target_dir)?;
+    Ok(target_dir.to_path_buf())
+}
+
+/// Moves the contents of `src` into `dst`, merging directories and replacing files.
+fn move_into(src: &Path, dst: &Path) -> Result<()> {
+    for entry in std::fs::read_dir(src)? {
+        let entry = entry?;
+        let target = dst.join(entry.file_name());
+        let is_dir = entry.file_type()?.is_dir();
+        if is_dir && target.is_dir() {
+            move_into(&entry.path(), &target)?;
+            continue;
+        }
+        if target.is_dir() {
+            std::fs::remove_dir_all(&target)?;
+        } else if target.symlink_metadata().is_ok() {
+            std::fs::remove_file(&target)?;
+        }
+        std::fs::rename(entry.path(), &target)?;
+    }
+    Ok(())
+}
+
+/// Moves the contents of the only directory in `dir` up to `dir`.
+fn strip_toplevel(dir: &Path) -> Result<()> {
+    let entries = std::fs::read_dir(dir)?.collect::<std::io::Result<Vec<_>>>()?;
+    let [entry] = entries.as_slice() else {
+        return Ok(());
+    };
+    if !entry.file_type()?.is_dir() {
+        return Ok(());
+    }
+    // Rename first, in case the directory contains an entry with its own name.
+    let toplevel = dir.join(format!(".{}.strip", entry.file_name().to_string_lossy()));
+    std::fs::rename(entry.path(), &toplevel)?;
+    for child in std::fs::read_dir(&toplevel)? {
+        let child = child?;
+        std::fs::rename(child.path(), dir.join(child.file_name()))?;
+    }
+    std::fs::remove_dir(&toplevel)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_tarball(path: &Path, files: &[(&str, &str)]) {
+        let encoder = flate2::write::GzEncoder::new(
+            File::create(path).unwrap(),
+            flate2::Compression::default(),
+        );
+        let mut builder = tar::Builder::new(encoder);
+        for (name, content) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, name, content.as_bytes())
+                .unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap();
+    }
+
+    #[test]
+    fn recognises_tarball_extensions() {
+        for name in ["a.tar.gz", "a.TGZ", "a.tar.xz", "a.txz"] {
+            assert!(is_tarball(Path::new(name)), "{}", name);
+        }
+        for name in ["a.json.gz", "a.xz", "a.zip", "a.tar"] {
+            assert!(!is_tarball(Path::new(name)), "{}", name);
+        }
+    }
+
+    #[test]
+    fn strips_the_toplevel_directory_into_a_non_empty_target() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = dir.path().join("mod.tar.gz");
+        write_tarball(
+            &archive,
+            &[
+                ("mod-1.0/modinfo.json", "new"),
+                ("mod-1.0/data/items.json", "items"),
+            ],
+        );
+        let target = dir.path().join("target");
+        std::fs::create_dir_all(target.join("data")).unwrap();
+        std::fs::write(target.join("modinfo.json"), "old").unwrap();
+        std::fs::write(target.join("data").join("kept.json"), "kept").unwrap();
+
+        untar(&archive, &target, &AtomicBool::new(false), |_| {}).unwrap();
+
+        let read = |path: &str| std::fs::read_to_string(target.join(path)).unwrap();
+        assert_eq!(read("modinfo.json"), "new");
+        assert_eq!(read("data/items.json"), "items");
+        assert_eq!(read("data/kept.json"), "kept");
+        let mut names = std::fs::read_dir(&target)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, ["data", "modinfo.json"]);
+    }
+
+    #[test]
+    fn keeps_several_toplevel_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = dir.path().join("game.tar.gz");
+        write_tarball(&archive, &[("a/one", "1"), ("b/two", "2")]);
+        let target = dir.path().join("target");
+
+        untar(&archive, &target, &AtomicBool::new(false), |_| {}).unwrap();
+
+        assert!(target.join("a").join("one").is_file());
+        assert!(target.join("b").join("two").is_file());
+    }
+
+    #[test]
+    fn cancelling_leaves_the_target_untouched() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = dir.path().join("game.tar.gz");
+        write_tarball(&archive, &[("game/one", "1"), ("game/two", "2")]);
+        let target = dir.path().join("target");
+        std::fs::create_dir_all(&target).unwrap();
+        std::fs::write(target.join("one"), "old").unwrap();
+
+        let result = untar(&archive, &target, &AtomicBool::new(true), |_| {});
+
+        assert_eq!(result.unwrap_err().to_string(), "Extraction cancelled");
+        assert_eq!(std::fs::read_to_string(target.join("one")).unwrap(), "old");
+        assert_eq!(std::fs::read_dir(&target).unwrap().count(), 1);
+    }
+}
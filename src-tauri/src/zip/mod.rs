@@ -2,7 +2,8 @@ use crate::prelude::*;
 
 use std::ffi::OsStr;
 use std::fs::File;
-use std::io::{BufReader, BufWriter};
+use std::io::{BufReader, BufWriter, Read, Seek};
+use std::sync::atomic::{AtomicBool, Ordering};
 use zifu_core::filename_decoder;
 use zifu_core::InputZIPArchive;
 
@@ -44,10 +45,37 @@ pub fn fix_zip_fname_encoding(file_path: String, output_zip_path: String) -> Res
     }
 }
 
-pub fn unzip(file_path: &Path, target_dir: &Path) -> Result<PathBuf> {
+/// Fails the reads of the archive once `cancel` is set, which aborts `zip_extract` mid-extraction.
+struct CancellableReader<'a, R> {
+    inner: R,
+    cancel: &'a AtomicBool,
+}
+
+impl<R: Read> Read for CancellableReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.cancel.load(Ordering::SeqCst) {
+            return Err(std::io::Error::other("Extraction cancelled"));
+        }
+        self.inner.read(buf)
+    }
+}
+
+impl<R: Seek> Seek for CancellableReader<'_, R> {
+    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+/// Extracts a zip archive into `target_dir`. Setting `cancel` stops the extraction with an error.
+pub fn unzip(file_path: &Path, target_dir: &Path, cancel: &AtomicBool) -> Result<PathBuf> {
     use zip_extract::extract;
     let archive = std::fs::read(file_path)?;
-    extract(std::io::Cursor::new(archive), target_dir, true)?;
+    let reader = CancellableReader {
+        inner: std::io::Cursor::new(archive),
+        cancel,
+    };
+    extract(reader, target_dir, true)?;
+    ensure!(!cancel.load(Ordering::SeqCst), "Extraction cancelled");
     Ok(target_dir.to_path_buf())
 }
 
@@ -61,12 +89,17 @@ fn to_stem(file_path: impl AsRef<Path>) -> String {
     if parts.len() > 1 {
         parts.pop();
     }
+    // foo.tar.gz -> foo
+    if parts.len() > 1 && parts.last() == Some(&"tar") {
+        parts.pop();
+    }
     parts.join(".")
 }
 
 pub mod commands {
     use super::*;
     use crate::files::{copy_dir_all, get_shallowest_mod_dir, remove_dir_all};
+    use crate::tarball::{is_tarball, untar};
     use tempfile::tempdir;
 
     struct SrcDestPaths {
@@ -127,15 +160,21 @@ pub mod commands {
     }
 
     /// Unzips a mod archive to a destination directory.
-    /// src: Mod archive file path
+    /// src: Mod archive file path (zip, tar.gz or tar.xz)
     /// dest_dir: profile's mod directory
     #[tauri::command]
     pub fn unzip_mod_archive(src: String, exists_ok: Option<bool>) -> Result<(), String> {
         let paths = prepare_paths(src, exists_ok).map_err(|e| e.to_string())?;
-        let fixed_zip_path = create_fixed_encoding_zip(&paths.src, paths.tmp_zip.path())
-            .map_err(|e| e.to_string())?;
         let tmp_dir_path = paths.tmp_extract.path().to_path_buf();
-        unzip(&fixed_zip_path, &tmp_dir_path).map_err(|e| e.to_string())?;
+        if is_tarball(&paths.src) {
+            untar(&paths.src, &tmp_dir_path, &AtomicBool::new(false), |_| {})
+                .map_err(|e| e.to_string())?;
+        } else {
+            let fixed_zip_path = create_fixed_encoding_zip(&paths.src, paths.tmp_zip.path())
+                .map_err(|e| e.to_string())?;
+            unzip(&fixed_zip_path, &tmp_dir_path, &AtomicBool::new(false))
+                .map_err(|e| e.to_string())?;
+        }
 
         let mod_dir = get_shallowest_mod_dir(&tmp_dir_path)
             .ok_or_else(|| "No mod directory found in archive".to_string())?;
@@ -152,12 +191,39 @@ pub mod commands {
             .map_err(|e| e.to_string())
     }
 
+    /// Extracts a zip or tarball archive. Tarball progress is emitted as `EXTRACT_PROGRESS`.
+    ///
+    /// Both strip a single top-level directory and overwrite existing files in `dest_dir`.
     #[tauri::command]
-    pub fn unzip_archive(src: String, dest_dir: String) -> Result<(), String> {
+    pub async fn unzip_archive(
+        handle: tauri::AppHandle,
+        src: String,
+        dest_dir: String,
+    ) -> Result<(), String> {
+        use tauri::Manager;
         let src_path = std::path::PathBuf::from(src);
         let dest_dir_path = std::path::PathBuf::from(dest_dir);
-        unzip(&src_path, &dest_dir_path)
-            .map_err(|e| e.to_string())
-            .map(|_| ())
+        tauri::async_runtime::spawn_blocking(move || {
+            if is_tarball(&src_path) {
+                let on_progress = |progress: crate::dmg::Progress| {
+                    handle
+                        .emit("EXTRACT_PROGRESS", progress)
+                        .unwrap_or_else(|e| warn!("Failed to emit extract progress: {}", e));
+                };
+                return untar(
+                    &src_path,
+                    &dest_dir_path,
+                    &AtomicBool::new(false),
+                    on_progress,
+                )
+                .map_err(|e| e.to_string())
+                .map(|_| ());
+            }
+            unzip(&src_path, &dest_dir_path, &AtomicBool::new(false))
+                .map_err(|e| e.to_string())
+                .map(|_| ())
+        })
+        .await
+        .map_err(|e| e.to_string())?
     }
 }
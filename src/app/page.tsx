@@ -19,7 +19,8 @@ import { refreshModsAtom, modsAtom } from "@/components/atoms";
 // Utils
 import { ask } from "@tauri-apps/plugin-dialog";
 import { popUp } from "@/lib/utils";
-import { unzipModArchive } from "@/lib/api";
+import { unzipModArchive, updateProfileGame } from "@/lib/api";
+import type { AvailableUpdate, RolledBack } from "@/lib/api";
 
 // animation
 import { AnimatePresence, motion } from "framer-motion";
@@ -142,6 +143,8 @@ export default function Home() {
 const initializeDragDropAndLogger = () => {
   if (!IS_LOGGER_ATTACHED) {
     handleDragDropEvent();
+    handleUpdateEvent();
+    handleRollbackEvent();
     IS_LOGGER_ATTACHED = true;
   }
 };
@@ -155,15 +158,42 @@ const handleDragDropEvent = async () => {
   };
 };
 
+const handleUpdateEvent = async () => {
+  const { listen } = await import("@tauri-apps/api/event");
+  return await listen<AvailableUpdate[]>("UPDATES_AVAILABLE", async (ev) => {
+    for (const update of ev.payload) {
+      const doesUpdate = await ask(
+        `${update.profile_name}: 新しいビルドがあります。\n${update.current_tag} → ${update.latest.tag_name}\n今のビルドを残したまま更新しますか？`,
+        "Catalyzer"
+      );
+      if (!doesUpdate) continue;
+      const install = await updateProfileGame(update.profile_id);
+      if (install) popUp("success", `${update.profile_name} を ${update.latest.tag_name} に更新しました。`);
+    }
+  });
+};
+
+const handleRollbackEvent = async () => {
+  const { listen } = await import("@tauri-apps/api/event");
+  return await listen<RolledBack>("ROLLED_BACK", (ev) => {
+    const build = ev.payload.build.install_id ?? ev.payload.build.game_path;
+    popUp("failed", `新しいビルドが初回起動でクラッシュしたため、${build} に戻しました。`);
+  });
+};
+
+/** Mod archives accepted by drag and drop. Matches `tarball::TARBALL_EXTENSIONS` and zip. */
+const ARCHIVE_EXTENSIONS = [".zip", ".tar.gz", ".tgz", ".tar.xz", ".txz"];
+
 const handleDragDropPayload = async (ev: any) => {
   if (ev.payload.type === "dropped") {
     const doesInstall = await ask(
-      `ZipファイルをModディレクトリに解凍しますか？\n${ev.payload.paths}`,
+      `アーカイブをModディレクトリに解凍しますか？\n${ev.payload.paths}`,
       "Catalyzer"
     );
     if (!doesInstall) return;
     const [filepath] = ev.payload.paths;
-    if (path.extname(filepath) === ".zip") {
+    const name = path.basename(filepath).toLowerCase();
+    if (ARCHIVE_EXTENSIONS.some((ext) => name.endsWith(ext))) {
       unzipModArchive(filepath);
       return;
     } else {
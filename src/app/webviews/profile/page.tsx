@@ -11,29 +11,37 @@ import { Table, TableBody, TableCell, TableHead, TableHeader, TableRow } from "@
 import { Tabs, TabsContent, TabsList, TabsTrigger } from "@/components/ui/tabs";
 import { ScrollArea } from "@/components/ui/scroll-area";
 import CSR from "@/components/csr/csr";
-import { download } from "@tauri-apps/plugin-upload";
 import { WebviewWindow } from "@tauri-apps/api/webviewWindow";
 
 import {
-  unzipArchive,
   addProfile,
   invoke_safe,
-  getPlatform,
   cddaStableReleases,
-  editProfile,
+  installRelease,
   cddaLatestReleases,
   removeProfile,
+  listReleaseSources,
 } from "@/lib/api";
+import type { ClassifiedAsset, InstallProgress, ReleaseSource } from "@/lib/api";
 
-import path from "path";
 import { listen } from "@tauri-apps/api/event";
 import { popUp } from "@/lib/utils";
 import { UpdateIcon } from "@radix-ui/react-icons";
 
 type Release = {
+  source: string;
   tag_name: string;
   browser_url: string;
   download_url: string;
+  size: number | null;
+  digest: string | null;
+  download_count: number | null;
+  published_at: string | null;
+  body: string | null;
+  prerelease: boolean;
+  commit_sha: string | null;
+  assets: ClassifiedAsset[];
+  selection_reason: string | null;
 };
 
 type InstallerProps = {
@@ -42,82 +50,63 @@ type InstallerProps = {
 const GameInstaller = ({ release }: InstallerProps) => {
   const [downloadProgress, setDownloadProgress] = useState<number>(0);
   const [extractProgress, setExtractProgress] = useState<number>(0);
-
-  const download_url = release.download_url;
+  const [assetUrl, setAssetUrl] = useState<string>(release.download_url);
 
   // info(`download: ${downloadProgress}%`);
 
   const downlaod_then_extract = async () => {
     const new_profile = await addProfile(release.tag_name);
     info(`created new profile ${JSON.stringify(new_profile)}`);
-    const fname = path.basename(download_url);
-    const download_save_path = path.join(new_profile.profile_path.root, fname);
-    const extract_dir = path.join(new_profile.profile_path.root, "game");
-    info(`
-      download_url: ${download_url}
-      fname: ${fname}
-      downlaod_save_path: ${download_save_path}
-      extract_dir: ${extract_dir}
-    `);
-
-    try {
-      let receivedLength = 0;
-      await download(download_url, download_save_path, ({ progress, total }) => {
-        setDownloadProgress(() => {
-          receivedLength += progress;
-          return Math.ceil((receivedLength / total) * 100);
-        });
-      });
-
-      if (receivedLength <= 1024 * 1024 * 10) {
-        setDownloadProgress(-1);
-        setExtractProgress(-1);
-        popUp("failed", "このバージョンはビルド中かもしれません。URLから確認してみてください。");
-        removeProfile(new_profile.id);
-        return;
-      }
 
-      type Payload = {
-        progress: number;
-        total: number;
-      };
-      WebviewWindow.getCurrent().listen<Payload>("EXTRACT_PROGRESS", (e) => {
-        let percent = Math.ceil((e.payload.progress / e.payload.total) * 100);
-        info(`extract: ${e.payload.progress} / ${e.payload.total} / ${percent}`);
-        setExtractProgress(() => percent);
-      });
-      if (download_url.endsWith(".zip")) {
-        await unzipArchive(download_save_path, extract_dir);
-      } else if (download_url.endsWith(".dmg")) {
-        await invoke_safe("extract_dmg", {
-          sourceDmg: download_save_path,
-          targetDir: extract_dir,
-        });
+    const unlisten = await WebviewWindow.getCurrent().listen<InstallProgress>("INSTALL_PROGRESS", (e) => {
+      const { stage, progress, total } = e.payload;
+      const percent = total > 0 ? Math.ceil((progress / total) * 100) : 0;
+      if (stage === "download") {
+        setDownloadProgress(percent);
+      } else if (stage === "extract") {
+        setDownloadProgress(100);
+        setExtractProgress(percent);
       }
-
-      info(`extracted to ${extract_dir} `);
-      // TODO: game path探し方をbackendに移動
-      const platform = await getPlatform();
-      let game_path =
-        {
-          windows: path.join(extract_dir, "cataclysm-tiles.exe"),
-          macos: path.join(extract_dir, "Cataclysm.app"),
-        }[platform] || null;
-      await editProfile(new_profile.id, new_profile.name, game_path);
+    });
+    // 既定以外のアセットが選ばれていれば差し替える
+    const asset = release.assets.find((a) => a.download_url === assetUrl);
+    const selected = asset
+      ? { ...release, download_url: asset.download_url, size: asset.size, digest: asset.digest }
+      : release;
+    try {
+      const install = await installRelease(selected, new_profile.id);
+      info(`installed to ${install.install_dir}`);
+      setDownloadProgress(100);
       setExtractProgress(100);
-      // unlisten();
     } catch (error) {
       warn(JSON.stringify(error));
       setDownloadProgress(-1);
       setExtractProgress(-1);
-      popUp("failed", "ダウンロードまたは解凍に失敗しました。");
+      popUp("failed", "ダウンロードまたは解凍に失敗しました。このバージョンはビルド中かもしれません。");
       removeProfile(new_profile.id);
+    } finally {
+      unlisten();
     }
   };
 
   return (
     <div className="flex flex-col justify-center items-center">
-      {!!release.download_url ? (
+      {release.assets.length > 1 && (
+        <select
+          className="text-xs border rounded px-1 py-0.5 mb-1 bg-background max-w-[240px]"
+          value={assetUrl}
+          title={release.selection_reason ?? undefined}
+          onChange={(e) => setAssetUrl(e.target.value)}
+        >
+          {!release.download_url && <option value="">アセットを選択</option>}
+          {release.assets.map((a) => (
+            <option key={a.download_url} value={a.download_url}>
+              {a.name}
+            </option>
+          ))}
+        </select>
+      )}
+      {!!assetUrl ? (
         <ProgressButton
           label="プロファイルを作る"
           download_progress={downloadProgress}
@@ -139,19 +128,28 @@ const GameInstaller = ({ release }: InstallerProps) => {
   );
 };
 
+type ReleaseList = {
+  releases: Release[];
+  from_cache: boolean;
+  stale: boolean;
+  fetched_at: string;
+};
+const emptyReleaseList: ReleaseList = { releases: [], from_cache: false, stale: false, fetched_at: "" };
+
 class ApiCaller {
   private minInterval: number = 60 * 5;
   private lastCallTime: number | null;
-  public latestReleasesCache: Release[] = [];
-  public stableReleasesCache: Release[] = [];
+  private lastSource: string | undefined;
+  public latestReleasesCache: ReleaseList = emptyReleaseList;
+  public stableReleasesCache: ReleaseList = emptyReleaseList;
 
   constructor() {
     this.lastCallTime = null;
   }
-  public async getStableRelease(): Promise<Release[]> {
+  public async getStableRelease(source?: string): Promise<ReleaseList> {
     // Rate Limit対策
     const currentTime = Date.now();
-    if (this.lastCallTime !== null) {
+    if (this.lastCallTime !== null && this.lastSource === source) {
       const timeSinceLastCall = (currentTime - this.lastCallTime) / 1000;
       if (timeSinceLastCall < this.minInterval) {
         info(
@@ -161,41 +159,48 @@ class ApiCaller {
       }
     }
     this.lastCallTime = currentTime;
-    const res = (await cddaStableReleases(5)) as Release[];
+    this.lastSource = source;
+    const res = (await cddaStableReleases(5, source)) as ReleaseList;
     this.stableReleasesCache = res;
     return Promise.resolve(this.stableReleasesCache);
   }
-  public async getLatestRelease(): Promise<Release[]> {
-    // Latest ReleaseはGithub APIを呼ばないのでRate Limitの制限はない
-    const res = (await cddaLatestReleases(10)) as Release[];
+  public async getLatestRelease(source?: string): Promise<ReleaseList> {
+    // Latest Releaseは1リクエストでまとめて取得し、バックエンドでキャッシュされる
+    const res = (await cddaLatestReleases(10, source)) as ReleaseList;
     this.latestReleasesCache = res;
     return Promise.resolve(this.latestReleasesCache);
   }
 }
 const cached_api = new ApiCaller();
 function Dashboard() {
-  const [stableReleases, setStableReleases] = useState<Release[]>([]);
-  const [latestReleases, setLatestReleases] = useState<Release[]>([]);
+  const [stableReleases, setStableReleases] = useState<ReleaseList>(emptyReleaseList);
+  const [latestReleases, setLatestReleases] = useState<ReleaseList>(emptyReleaseList);
   const [rateLimit, setRateLimit] = useState<number | null>(null);
+  const [sources, setSources] = useState<ReleaseSource[]>([]);
+  const [source, setSource] = useState<string | undefined>(undefined);
 
   const handleGetStableReleases = async () => {
-    cached_api.getStableRelease().then((res) => {
+    cached_api.getStableRelease(source).then((res) => {
       setStableReleases(res);
     });
     fetchRateLimit();
   };
 
   const handleGetLatestReleases = async () => {
-    cached_api.getLatestRelease().then((res) => {
+    cached_api.getLatestRelease(source).then((res) => {
       setLatestReleases(res);
     });
   };
 
   useEffect(() => {
     fetchRateLimit();
-    handleGetLatestReleases();
+    listReleaseSources().then(setSources);
   }, []);
 
+  useEffect(() => {
+    handleGetLatestReleases();
+  }, [source]);
+
   const fetchRateLimit = async () => {
     const res = await invoke_safe<number>("github_rate_limit", {});
     setRateLimit(res);
@@ -214,6 +219,19 @@ function Dashboard() {
             >
               {release.tag_name}
             </Link>
+            {release.prerelease && <span className="ml-1 text-xs text-amber-600">pre-release</span>}
+            <div className="text-xs text-muted-foreground">
+              {release.published_at && new Date(release.published_at).toLocaleDateString()}
+              {release.size !== null && ` · ${(release.size / 1024 / 1024).toFixed(0)} MB`}
+              {release.download_count !== null && ` · ${release.download_count} DL`}
+              {release.commit_sha && ` · ${release.commit_sha.slice(0, 7)}`}
+            </div>
+            {release.body && (
+              <details className="text-xs">
+                <summary className="cursor-pointer">リリースノート</summary>
+                <pre className="whitespace-pre-wrap max-h-40 overflow-auto">{release.body}</pre>
+              </details>
+            )}
           </TableCell>
           <TableCell>
             <GameInstaller release={release} />
@@ -251,7 +269,32 @@ function Dashboard() {
                   Stable
                 </TabsTrigger>
               </TabsList>
+              {sources.length > 1 && (
+                <select
+                  className="text-xs border rounded px-1 py-0.5 bg-background"
+                  value={source ?? sources[0].id}
+                  onChange={(e) => setSource(e.target.value)}
+                >
+                  {sources.map((s) => (
+                    <option key={s.id} value={s.id}>
+                      {s.name}
+                    </option>
+                  ))}
+                </select>
+              )}
               <div className="text-xs">APIコール制限残: {rateLimit !== null ? rateLimit : "Loading..."}</div>
+              {(latestReleases.stale || stableReleases.stale) && (
+                <div className="text-xs text-amber-600">
+                  オフラインまたはAPI制限中のため、キャッシュを表示しています (
+                  {new Date(
+                    [latestReleases, stableReleases]
+                      .filter((l) => l.stale)
+                      .map((l) => l.fetched_at)
+                      .sort()[0],
+                  ).toLocaleString()}
+                  )
+                </div>
+              )}
             </div>
             <TabsContent value="latest">
               <CSR>
@@ -266,7 +309,7 @@ function Dashboard() {
                       </TableHeader>
                       <TableBody>
                         <ScrollArea className="h-[250px]">
-                          {latestReleases.map((release) => {
+                          {latestReleases.releases.map((release) => {
                             return <ReleaseRow key={release.tag_name} release={release} />;
                           })}
                         </ScrollArea>
@@ -289,7 +332,7 @@ function Dashboard() {
                       </TableHeader>
                       <TableBody>
                         <ScrollArea className="h-[200px]">
-                          {stableReleases.map((release) => {
+                          {stableReleases.releases.map((release) => {
                             return <ReleaseRow key={release.tag_name} release={release} />;
                           })}
                         </ScrollArea>
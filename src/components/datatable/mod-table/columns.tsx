@@ -351,7 +351,7 @@ export const columns: ColumnDef<Mod>[] = [
                                 const selected = await open({
                                   directory: false,
                                   multiple: false,
-                                  filters: [{ name: "Zip", extensions: ["zip"] }],
+                                  filters: [{ name: "Archive", extensions: ["zip", "gz", "tgz", "xz", "txz"] }],
                                   defaultPath: await downloadDir(),
                                 });
                                 if (selected == null || Array.isArray(selected)) {
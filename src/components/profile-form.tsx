@@ -26,8 +26,10 @@ const profileFormSchema = z.object({
     .max(255)
     .trim()
     .refine((value) => {
-      return value.endsWith("cataclysm-tiles.exe") || value.endsWith("Cataclysm.app");
-    }, "Game path must end with cataclysm-tiles.exe or Cataclysm.app"),
+      return (
+        value.endsWith("cataclysm-tiles.exe") || value.endsWith("Cataclysm.app") || value.endsWith("cataclysm-tiles")
+      );
+    }, "Game path must end with cataclysm-tiles.exe, Cataclysm.app or cataclysm-tiles"),
 });
 
 type ProfileFormProps = {
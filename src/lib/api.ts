@@ -71,17 +71,220 @@ const setLanguage = async (i18n: ReturnType<typeof useTranslation>["i18n"], lang
 
 const tailLog = async () => await invoke_safe<String[]>("tail_log", {});
 
-const cddaStableReleases = async (num: number) => await invoke_safe("cdda_get_stable_releases", { num: num });
-const cddaLatestReleases = async (num: number) => await invoke_safe("cdda_get_latest_releases", { num: num });
+const cddaStableReleases = async (num: number, source?: string) =>
+  await invoke_safe("cdda_get_stable_releases", { num: num, source: source });
+const cddaLatestReleases = async (num: number, source?: string) =>
+  await invoke_safe("cdda_get_latest_releases", { num: num, source: source });
+
+type ReleaseSource = {
+  id: string;
+  name: string;
+  location:
+    | { kind: "github"; repo: string; web_url: string; api_url: string }
+    | { kind: "mirror"; index_url: string };
+  stable_tag_pattern: string;
+  experimental_tag_pattern: string;
+  asset_patterns: Record<string, string>;
+  experimental_url_templates: Record<string, string>;
+};
+const setGithubToken = async (token: string | null) => await invoke_safe("set_github_token", { token: token });
+const listReleaseSources = async () => await invoke_safe<ReleaseSource[]>("list_release_sources", {});
+const setReleaseSources = async (sources: ReleaseSource[]) =>
+  await invoke_safe("set_release_sources", { sources: sources });
 const isCddaCloned = async () => await invoke_safe("cdda_is_cloned", {});
 const cddaPullRebase = async () => await invoke_safe("cdda_pull_rebase", {});
+type SparseCheckout = { enabled: boolean; paths: string[] };
+/** Changes the paths checked out in the CDDA clone, and applies them to an existing clone. */
+const setCddaSparseCheckout = async (sparse: SparseCheckout) =>
+  await invoke_safe("set_cdda_sparse_checkout", { sparse: sparse });
+
+type ChangeCategory =
+  | "features"
+  | "content"
+  | "interface"
+  | "mods"
+  | "balance"
+  | "bugfixes"
+  | "performance"
+  | "infrastructure"
+  | "build"
+  | "i18n"
+  | "other";
+type ChangelogCommit = { sha: string; title: string; pr_number: number | null; author: string; time: string };
+type Changelog = {
+  from: string;
+  to: string;
+  groups: Partial<Record<ChangeCategory, ChangelogCommit[]>>;
+  changed_files: { path: string; change: "added" | "modified" | "deleted" | "renamed" }[];
+};
+/** `from` and `to` are tags or install ids. */
+const cddaChangelog = async (from: string, to: string) =>
+  await invoke_safe<Changelog>("cdda_changelog", { from: from, to: to });
 
 const getPlatform = async (): Promise<string> => await invoke_safe("get_platform", {});
 
 const unzipArchive = async (src: string, dest: string) =>
   await invoke_safe("unzip_archive", { src: src, destDir: dest });
 
-const printModJsonErrors = async () => await invoke_safe("inspect_mods", {});
+type ReleaseChannel = "stable" | "experimental" | "local";
+type GameInstall = {
+  id: string;
+  tag: string | null;
+  channel: ReleaseChannel;
+  platform: string;
+  install_dir: string;
+  game_path: string;
+  version: string | null;
+  /** Parsed game version, e.g. "0.H-1" or "2024-03-01-0613". */
+  cdda_version: string | null;
+  installed_at: string;
+  size_bytes: number;
+  pinned: boolean;
+};
+type GameInstallEntry = GameInstall & { used_by: string[] };
+const listGameInstalls = async () => await invoke_safe<GameInstallEntry[]>("list_game_installs", {});
+const registerGameInstall = async (installDir: string, tag?: string, profileId?: string) =>
+  await invoke_safe<GameInstall>("register_game_install", { installDir: installDir, tag: tag, profileId: profileId });
+const unregisterGameInstall = async (installId: string) =>
+  await invoke_safe("unregister_game_install", { installId: installId });
+const setProfileGameInstall = async (profileId: string, installId: string) =>
+  await invoke_safe("set_profile_game_install", { profileId: profileId, installId: installId });
+
+type InstallProgress = {
+  source: string;
+  tag_name: string;
+  stage: "download" | "verify" | "extract" | "register";
+  progress: number;
+  total: number;
+};
+type AssetVariant = {
+  platform: "windows" | "macos" | "linux" | "android" | "unknown";
+  arch: "x64" | "x86" | "arm64" | "universal" | "unknown";
+  frontend: "tiles" | "curses" | "unknown";
+  sounds: boolean;
+};
+type ClassifiedAsset = {
+  name: string;
+  download_url: string;
+  size: number | null;
+  digest: string | null;
+  download_count: number | null;
+  variant: AssetVariant;
+};
+type AssetPreference = {
+  frontend?: AssetVariant["frontend"] | null;
+  sounds?: boolean | null;
+  arch?: AssetVariant["arch"] | null;
+};
+type ReleaseInfo = {
+  source: string;
+  tag_name: string;
+  version: string | null;
+  browser_url: string;
+  download_url: string;
+  size: number | null;
+  digest: string | null;
+  download_count: number | null;
+  published_at: string | null;
+  body: string | null;
+  prerelease: boolean;
+  commit_sha: string | null;
+  assets: ClassifiedAsset[];
+  selection_reason: string | null;
+};
+const getAssetPreference = async () => await invoke_safe<AssetPreference>("get_asset_preference", {});
+const setAssetPreference = async (preference: AssetPreference) =>
+  await invoke_safe("set_asset_preference", { preference: preference });
+const installRelease = async (release: ReleaseInfo, profileId?: string) =>
+  await invoke_safe<GameInstall>("install_release", { release: release, profileId: profileId });
+const cancelInstallRelease = async (source: string, tagName: string) =>
+  await invoke_safe("cancel_install_release", { source: source, tagName: tagName });
+
+type RetentionPolicy = { keep_per_channel: number; keep_pinned: boolean; download_cache_days: number };
+type CleanupPlan = {
+  remove: GameInstall[];
+  keep: [GameInstall, "recent" | "used_by_profile" | "rollback_target" | "pinned" | "unmanaged"][];
+  freed_bytes: number;
+  cache_freed_bytes: number;
+};
+const planInstallCleanup = async (policy?: RetentionPolicy) =>
+  await invoke_safe<CleanupPlan>("plan_install_cleanup", { policy: policy });
+const applyInstallCleanup = async (policy?: RetentionPolicy) =>
+  await invoke_safe<CleanupPlan>("apply_install_cleanup", { policy: policy });
+const setRetentionPolicy = async (policy: RetentionPolicy) =>
+  await invoke_safe("set_retention_policy", { policy: policy });
+const setGameInstallPinned = async (installId: string, pinned: boolean) =>
+  await invoke_safe("set_game_install_pinned", { installId: installId, pinned: pinned });
+
+type AvailableUpdate = {
+  profile_id: string;
+  profile_name: string;
+  install_id: string;
+  current_tag: string;
+  latest: ReleaseInfo;
+};
+type UpdateCheckSettings = { enabled: boolean; interval_minutes: number };
+const listAvailableUpdates = async () => await invoke_safe<AvailableUpdate[]>("list_available_updates", {});
+const checkForUpdates = async () => await invoke_safe<AvailableUpdate[]>("check_for_updates", {});
+const updateProfileGame = async (profileId: string) =>
+  await invoke_safe<GameInstall>("update_profile_game", { profileId: profileId });
+const setProfileUpdateChannel = async (profileId: string, channel: ReleaseChannel | null) =>
+  await invoke_safe("set_profile_update_channel", { profileId: profileId, channel: channel });
+const setUpdateCheckSettings = async (updateCheck: UpdateCheckSettings) =>
+  await invoke_safe("set_update_check_settings", { updateCheck: updateCheck });
+
+type BuildSettings = {
+  checkouts: string[];
+  system: "make" | "cmake";
+  jobs: number | null;
+  extra_args: string[];
+};
+/** The checkouts that can be built, the CDDA clone first. */
+const listBuildSources = async () => await invoke_safe<string[]>("list_build_sources", {});
+/**
+ * Builds the game from a source checkout, given as a path or the directory name of a build source
+ * (the CDDA clone if omitted). The compiler output is emitted line by line as `BUILD_OUTPUT`. Linux only.
+ */
+const buildGame = async (sourceDir?: string, profileId?: string) =>
+  await invoke_safe<GameInstall>("build_game", { sourceDir: sourceDir, profileId: profileId });
+const cancelBuildGame = async () => await invoke_safe("cancel_build_game", {});
+const setBuildSettings = async (build: BuildSettings) => await invoke_safe("set_build_settings", { build: build });
+
+type PreviousBuild = {
+  install_id: string | null;
+  game_path: string | null;
+  switched_at: string;
+  save_backup: string | null;
+  launched: boolean;
+};
+/** Payload of the ROLLED_BACK event. */
+type RolledBack = { profile_id: string; build: PreviousBuild };
+const rollbackProfileGame = async (profileId: string, restoreSaves?: boolean) =>
+  await invoke_safe<PreviousBuild>("rollback_profile_game", { profileId: profileId, restoreSaves: restoreSaves });
+const setProfileAutoRollback = async (profileId: string, enabled: boolean) =>
+  await invoke_safe("set_profile_auto_rollback", { profileId: profileId, enabled: enabled });
+
+type ModDiagnostic = {
+  mod_id: string | null;
+  severity: "error" | "warning";
+  message: string;
+  file: string | null;
+  line: number | null;
+  column: number | null;
+};
+type InspectModsResult = {
+  targets: string[];
+  game_version: string | null;
+  diagnostics: ModDiagnostic[];
+  artifact: string | null;
+};
+const inspectMods = async (saveArtifact?: boolean) =>
+  await invoke_safe<InspectModsResult>("inspect_mods", { saveArtifact: saveArtifact });
+const printModJsonErrors = async () => {
+  const res = await inspectMods(true);
+  if (res?.artifact) await openLocalDir(res.artifact);
+  return res;
+};
 
 export {
   // re-export
@@ -96,11 +299,41 @@ export {
   removeProfile,
   editProfile,
 
+  // game installs
+  listGameInstalls,
+  registerGameInstall,
+  unregisterGameInstall,
+  setProfileGameInstall,
+  installRelease,
+  cancelInstallRelease,
+  planInstallCleanup,
+  applyInstallCleanup,
+  setRetentionPolicy,
+  setGameInstallPinned,
+  listAvailableUpdates,
+  checkForUpdates,
+  updateProfileGame,
+  setProfileUpdateChannel,
+  setUpdateCheckSettings,
+  rollbackProfileGame,
+  setProfileAutoRollback,
+  listBuildSources,
+  buildGame,
+  cancelBuildGame,
+  setBuildSettings,
+
   // git for cdda
   cddaStableReleases,
   cddaLatestReleases,
+  listReleaseSources,
+  setReleaseSources,
+  setGithubToken,
+  getAssetPreference,
+  setAssetPreference,
   isCddaCloned,
   cddaPullRebase,
+  setCddaSparseCheckout,
+  cddaChangelog,
 
   // git general purpose
   gitFetch,
@@ -123,5 +356,9 @@ export {
   uninstallAllMods,
   listMods,
   tailLog,
+  inspectMods,
   printModJsonErrors,
 };
+export type { ModDiagnostic, InspectModsResult, ReleaseChannel, GameInstall, GameInstallEntry, InstallProgress, ReleaseInfo, ReleaseSource };
+export type { ClassifiedAsset, AssetPreference, AvailableUpdate, UpdateCheckSettings, RetentionPolicy, CleanupPlan };
+export type { SparseCheckout, BuildSettings, PreviousBuild, RolledBack, ChangeCategory, ChangelogCommit, Changelog };